@@ -0,0 +1,257 @@
+use crate::features;
+use crate::tetris::*;
+
+// Scoring a board for the bot: the board of the game already contains the placed piece,
+// before the filled lines are cleared. A higher value is a better board, the value can be
+// negative.
+pub trait Evaluator: Sync {
+    fn evaluate(&self, game: &Game, piece: &Piece) -> f64;
+}
+
+// The board features an evaluator can weight
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum Feature {
+    // the original features of the bot
+    HoleFactor,
+    Bumpiness,
+    MaxHeight,
+    LineContinuity,
+    LineFilledness,
+    FilledLines,
+    // the features of the classic Tetris bots, see features.rs
+    LandingHeight,
+    ErodedPieceCells,
+    AggregateHeight,
+    RowTransitions,
+    ColumnTransitions,
+    CumulativeWells,
+    Holes,
+    HoleDepth,
+    RowsWithHoles,
+}
+
+// the features (and their order) the original six parameter fitness used
+pub const CLASSIC_FEATURES: [Feature; 6] = [
+    Feature::HoleFactor,
+    Feature::Bumpiness,
+    Feature::MaxHeight,
+    Feature::LineContinuity,
+    Feature::LineFilledness,
+    Feature::FilledLines,
+];
+
+impl Feature {
+    pub const ALL: [Feature; 15] = [
+        Feature::HoleFactor,
+        Feature::Bumpiness,
+        Feature::MaxHeight,
+        Feature::LineContinuity,
+        Feature::LineFilledness,
+        Feature::FilledLines,
+        Feature::LandingHeight,
+        Feature::ErodedPieceCells,
+        Feature::AggregateHeight,
+        Feature::RowTransitions,
+        Feature::ColumnTransitions,
+        Feature::CumulativeWells,
+        Feature::Holes,
+        Feature::HoleDepth,
+        Feature::RowsWithHoles,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Feature::HoleFactor => "hole_factor",
+            Feature::Bumpiness => "bumpiness",
+            Feature::MaxHeight => "max_height",
+            Feature::LineContinuity => "line_continuity",
+            Feature::LineFilledness => "line_filledness",
+            Feature::FilledLines => "filled_lines",
+            Feature::LandingHeight => "landing_height",
+            Feature::ErodedPieceCells => "eroded_piece_cells",
+            Feature::AggregateHeight => "aggregate_height",
+            Feature::RowTransitions => "row_transitions",
+            Feature::ColumnTransitions => "column_transitions",
+            Feature::CumulativeWells => "cumulative_wells",
+            Feature::Holes => "holes",
+            Feature::HoleDepth => "hole_depth",
+            Feature::RowsWithHoles => "rows_with_holes",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Result<Feature, String> {
+        Feature::ALL
+            .iter()
+            .find(|feature| feature.name() == name)
+            .copied()
+            .ok_or(format!("unknown feature '{}'", name))
+    }
+
+    // the value of the feature on the board of the game
+    pub fn value(self, game: &Game, piece: &Piece) -> f64 {
+        let board = &game.board;
+
+        let value = match self {
+            Feature::HoleFactor => game.blocks_hole_factor().pow(2),
+            Feature::Bumpiness => game.blocks_bumpiness().0,
+            Feature::MaxHeight => game.blocks_bumpiness().1,
+            Feature::LineContinuity => game.block_line_continuity(),
+            Feature::LineFilledness => game.block_line_filledness(),
+            Feature::FilledLines => (features::filled_lines(board) * 10).pow(2),
+            Feature::LandingHeight => return features::landing_height(piece),
+            Feature::ErodedPieceCells => features::eroded_piece_cells(board, piece),
+            Feature::AggregateHeight => features::aggregate_height(board),
+            Feature::RowTransitions => features::row_transitions(board),
+            Feature::ColumnTransitions => features::column_transitions(board),
+            Feature::CumulativeWells => features::cumulative_wells(board),
+            Feature::Holes => features::holes(board),
+            Feature::HoleDepth => features::hole_depth(board),
+            Feature::RowsWithHoles => features::rows_with_holes(board),
+        };
+        value as f64
+    }
+
+    // The value of the feature divided by the largest value it can (roughly) reach, so it
+    // is between 0 and 1 and the weights of different features can be compared.
+    pub fn normalized_value(self, game: &Game, piece: &Piece) -> f64 {
+        self.value(game, piece) / self.max_value()
+    }
+
+    fn max_value(self) -> f64 {
+        let width = WIDTH as f64;
+        let height = HEIGHT as f64;
+        // sum of the row indices, the classic features weight the rows with them
+        let row_sum = height * (height - 1.0) / 2.0;
+
+        match self {
+            Feature::HoleFactor => (width * row_sum).powi(2),
+            Feature::Bumpiness => (width - 1.0) * height,
+            Feature::MaxHeight => height,
+            Feature::LineContinuity => width * width * row_sum,
+            Feature::LineFilledness => width * width * row_sum,
+            // 4 lines at once, see Feature::value
+            Feature::FilledLines => 1600.0,
+            Feature::LandingHeight => height,
+            Feature::ErodedPieceCells => 16.0,
+            Feature::AggregateHeight => width * height,
+            Feature::RowTransitions => (width + 1.0) * height,
+            Feature::ColumnTransitions => width * (height + 1.0),
+            Feature::CumulativeWells => width * height * (height + 1.0) / 2.0,
+            Feature::Holes => width * height,
+            Feature::HoleDepth => width * row_sum,
+            Feature::RowsWithHoles => height,
+        }
+    }
+
+    // whether a higher value of the feature is a better board
+    fn is_reward(self) -> bool {
+        matches!(
+            self,
+            Feature::LineContinuity
+                | Feature::LineFilledness
+                | Feature::FilledLines
+                | Feature::ErodedPieceCells
+        )
+    }
+}
+
+// Weighted sum of the selected features
+#[derive(Clone, Debug)]
+pub struct LinearEvaluator {
+    pub weights: Vec<(Feature, f64)>,
+}
+
+impl LinearEvaluator {
+    // the features with their weights, in the same order
+    pub fn new(features: &[Feature], weights: &[f64]) -> Self {
+        assert_eq!(features.len(), weights.len());
+
+        LinearEvaluator {
+            weights: features
+                .iter()
+                .copied()
+                .zip(weights.iter().copied())
+                .collect(),
+        }
+    }
+
+    // the original six parameter fitness of the bot
+    pub fn classic(weights: [f64; 6]) -> Self {
+        LinearEvaluator::new(&CLASSIC_FEATURES, &weights)
+    }
+
+    pub fn weight(&self, name: &str) -> Option<f64> {
+        self.weights
+            .iter()
+            .find(|(feature, _)| feature.name() == name)
+            .map(|(_, weight)| *weight)
+    }
+}
+
+impl Evaluator for LinearEvaluator {
+    fn evaluate(&self, game: &Game, piece: &Piece) -> f64 {
+        let mut fitness = 0.0;
+
+        for (feature, weight) in self.weights.iter() {
+            let value = weight * feature.normalized_value(game, piece);
+            if feature.is_reward() {
+                fitness += value;
+            } else {
+                fitness -= value;
+            }
+        }
+
+        fitness
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_feature_names() {
+        for feature in Feature::ALL.iter() {
+            assert_eq!(Feature::from_name(feature.name()), Ok(*feature));
+        }
+        assert!(Feature::from_name("nothing").is_err());
+    }
+
+    #[test]
+    fn test_linear_evaluator() {
+        let evaluator = LinearEvaluator::new(&[Feature::Holes, Feature::FilledLines], &[2.0, 0.5]);
+        assert_eq!(evaluator.weight("holes"), Some(2.0));
+        assert_eq!(evaluator.weight("bumpiness"), None);
+
+        let mut game = Game::new();
+        let piece = game.curr_piece;
+        assert_eq!(evaluator.evaluate(&game, &piece), 0.0);
+
+        // a hole costs, a filled line pays
+        game.board[HEIGHT as usize - 1] = vec![1; WIDTH as usize];
+        game.board[HEIGHT as usize - 3][0] = 1;
+        let holes = 1.0 / (WIDTH as f64 * HEIGHT as f64);
+        let filled_lines = 100.0 / 1600.0;
+        assert_eq!(
+            evaluator.evaluate(&game, &piece),
+            -2.0 * holes + 0.5 * filled_lines
+        );
+    }
+
+    #[test]
+    fn test_no_overflow() {
+        // the worst board possible with huge weights still gives a finite, negative score
+        let evaluator = LinearEvaluator::new(&Feature::ALL, &[1e12; 15]);
+        let mut game = Game::new();
+        for row in 1..HEIGHT as usize {
+            for col in 0..WIDTH as usize {
+                game.board[row][col] = ((row + col) % 2) as u8;
+            }
+        }
+        let piece = game.curr_piece;
+
+        let fitness = evaluator.evaluate(&game, &piece);
+        assert!(fitness.is_finite());
+        assert!(fitness < 0.0);
+    }
+}
@@ -0,0 +1,416 @@
+use crate::replay::*;
+use crate::tetris::*;
+use std::collections::HashMap;
+use std::fs;
+
+// Image export which doesn't need a window: PNG pictures of boards and animated GIFs of
+// replays. Both encoders are simple, the images only have a handful of colors.
+
+// size of a block in pixels
+const BLOCK_SIZE: usize = 16;
+// time between two GIF frames in hundredths of a second, a frame is a locked piece
+const GIF_FRAME_DELAY: u16 = 10;
+
+pub enum Theme {
+    // the colors of the SDL window
+    Classic,
+    // the usual colors of the pieces: S green, T purple, Z red, L orange, O yellow,
+    // J blue and I cyan
+    Guideline,
+}
+
+impl Theme {
+    pub fn from_name(name: &str) -> Result<Theme, String> {
+        match name {
+            "classic" => Ok(Theme::Classic),
+            "guideline" => Ok(Theme::Guideline),
+            _ => Err(format!("unknown theme '{}'", name)),
+        }
+    }
+
+    // colors of the board values: the empty block, then the piece kinds
+    fn palette(&self) -> Vec<[u8; 3]> {
+        match self {
+            Theme::Classic => (0..=7u8)
+                .map(|block| match block {
+                    0 => [0, 0, 0],
+                    _ => [128, block * (255 / 7), block * (255 / 7)],
+                })
+                .collect(),
+            Theme::Guideline => vec![
+                [0, 0, 0],
+                [0, 240, 0],
+                [160, 0, 240],
+                [240, 0, 0],
+                [240, 160, 0],
+                [240, 240, 0],
+                [0, 0, 240],
+                [0, 240, 240],
+            ],
+        }
+    }
+}
+
+// An image where every pixel is an index into the palette
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u8>,
+}
+
+impl Image {
+    // The board with the current piece on it
+    pub fn from_game(game: &Game) -> Self {
+        let board = game.board_with_current_piece();
+        let width = WIDTH as usize * BLOCK_SIZE;
+        let height = HEIGHT as usize * BLOCK_SIZE;
+        let mut pixels = vec![0u8; width * height];
+
+        for (idx, pixel) in pixels.iter_mut().enumerate() {
+            let (row, col) = (idx / width, idx % width);
+            *pixel = board[row / BLOCK_SIZE][col / BLOCK_SIZE];
+        }
+
+        Image {
+            width,
+            height,
+            pixels,
+        }
+    }
+}
+
+pub fn save_png(game: &Game, theme: &Theme, path: &str) -> Result<(), String> {
+    fs::write(path, encode_png(&Image::from_game(game), &theme.palette()))
+        .map_err(|e| e.to_string())
+}
+
+// Saving the replay as an animated GIF, with a frame for every locked piece
+pub fn save_replay_gif(replay: Replay, theme: &Theme, path: &str) -> Result<(), String> {
+    let mut player = ReplayPlayer::new(replay);
+    let mut frames = vec![Image::from_game(&player.game)];
+    let mut pieces = player.game.pieces;
+
+    while player.step() {
+        if player.game.pieces != pieces {
+            pieces = player.game.pieces;
+            frames.push(Image::from_game(&player.game));
+        }
+    }
+    frames.push(Image::from_game(&player.game));
+
+    fs::write(path, encode_gif(&frames, &theme.palette(), GIF_FRAME_DELAY))
+        .map_err(|e| e.to_string())
+}
+
+// =====
+//  PNG
+// =====
+
+pub fn encode_png(image: &Image, palette: &[[u8; 3]]) -> Vec<u8> {
+    let mut png = vec![0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a];
+
+    // 8 bit palette indexes, without interlacing
+    let mut header = Vec::new();
+    header.extend_from_slice(&(image.width as u32).to_be_bytes());
+    header.extend_from_slice(&(image.height as u32).to_be_bytes());
+    header.extend_from_slice(&[8, 3, 0, 0, 0]);
+    push_png_chunk(&mut png, b"IHDR", &header);
+
+    push_png_chunk(&mut png, b"PLTE", &palette.concat());
+
+    // every row starts with its filter type, which is 0 (none) here
+    let mut rows = Vec::with_capacity((image.width + 1) * image.height);
+    for row in image.pixels.chunks(image.width) {
+        rows.push(0);
+        rows.extend_from_slice(row);
+    }
+    push_png_chunk(&mut png, b"IDAT", &zlib_stored(&rows));
+
+    push_png_chunk(&mut png, b"IEND", &[]);
+
+    png
+}
+
+fn push_png_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    let crc = crc32(&png[start..]);
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+// zlib stream made of uncompressed deflate blocks
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let mut zlib = vec![0x78, 0x01];
+    let mut chunks = data.chunks(0xffff).peekable();
+
+    if chunks.peek().is_none() {
+        zlib.extend_from_slice(&[1, 0, 0, 0xff, 0xff]);
+    }
+    while let Some(chunk) = chunks.next() {
+        let last = chunks.peek().is_none();
+        zlib.push(last as u8);
+        zlib.extend_from_slice(&(chunk.len() as u16).to_le_bytes());
+        zlib.extend_from_slice(&(!(chunk.len() as u16)).to_le_bytes());
+        zlib.extend_from_slice(chunk);
+    }
+
+    zlib.extend_from_slice(&adler32(data).to_be_bytes());
+    zlib
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xffff_ffffu32;
+    for byte in data.iter() {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for byte in data.iter() {
+        a = (a + *byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
+
+// =====
+//  GIF
+// =====
+
+// the palette is padded to this many colors
+const GIF_COLORS: usize = 16;
+const GIF_MIN_CODE_SIZE: u8 = 4;
+
+// Animated GIF playing the frames in a loop, @delay is in hundredths of a second
+pub fn encode_gif(frames: &[Image], palette: &[[u8; 3]], delay: u16) -> Vec<u8> {
+    let mut gif = b"GIF89a".to_vec();
+    let (width, height) = (frames[0].width as u16, frames[0].height as u16);
+
+    // logical screen with a global color table of 16 colors
+    gif.extend_from_slice(&width.to_le_bytes());
+    gif.extend_from_slice(&height.to_le_bytes());
+    gif.extend_from_slice(&[0xf3, 0, 0]);
+    for idx in 0..GIF_COLORS {
+        gif.extend_from_slice(palette.get(idx).unwrap_or(&[0, 0, 0]));
+    }
+
+    // looping forever
+    gif.extend_from_slice(&[0x21, 0xff, 0x0b]);
+    gif.extend_from_slice(b"NETSCAPE2.0");
+    gif.extend_from_slice(&[0x03, 0x01, 0x00, 0x00, 0x00]);
+
+    for frame in frames.iter() {
+        // graphic control extension with the frame delay
+        gif.extend_from_slice(&[0x21, 0xf9, 0x04, 0x00]);
+        gif.extend_from_slice(&delay.to_le_bytes());
+        gif.extend_from_slice(&[0x00, 0x00]);
+
+        // image descriptor covering the whole screen
+        gif.push(0x2c);
+        gif.extend_from_slice(&[0, 0, 0, 0]);
+        gif.extend_from_slice(&(frame.width as u16).to_le_bytes());
+        gif.extend_from_slice(&(frame.height as u16).to_le_bytes());
+        gif.push(0);
+
+        gif.push(GIF_MIN_CODE_SIZE);
+        for block in lzw_encode(&frame.pixels, GIF_MIN_CODE_SIZE).chunks(255) {
+            gif.push(block.len() as u8);
+            gif.extend_from_slice(block);
+        }
+        gif.push(0);
+    }
+
+    gif.push(0x3b);
+    gif
+}
+
+// Writing variable length codes with the least significant bit first
+struct BitWriter {
+    bytes: Vec<u8>,
+    buffer: u32,
+    bits: u8,
+}
+
+impl BitWriter {
+    fn write(&mut self, code: u16, size: u8) {
+        self.buffer |= (code as u32) << self.bits;
+        self.bits += size;
+        while self.bits >= 8 {
+            self.bytes.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.bits -= 8;
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.bits > 0 {
+            self.bytes.push(self.buffer as u8);
+        }
+        self.bytes
+    }
+}
+
+fn lzw_encode(pixels: &[u8], min_code_size: u8) -> Vec<u8> {
+    let clear_code = 1u16 << min_code_size;
+    let end_code = clear_code + 1;
+
+    let mut writer = BitWriter {
+        bytes: Vec::new(),
+        buffer: 0,
+        bits: 0,
+    };
+    let mut dictionary: HashMap<(u16, u8), u16> = HashMap::new();
+    let mut next_code = end_code + 1;
+    let mut code_size = min_code_size + 1;
+
+    writer.write(clear_code, code_size);
+
+    let mut prefix = match pixels.first() {
+        Some(pixel) => *pixel as u16,
+        None => {
+            writer.write(end_code, code_size);
+            return writer.finish();
+        }
+    };
+
+    for pixel in pixels[1..].iter() {
+        if let Some(code) = dictionary.get(&(prefix, *pixel)) {
+            prefix = *code;
+            continue;
+        }
+
+        writer.write(prefix, code_size);
+
+        if next_code < 4096 {
+            dictionary.insert((prefix, *pixel), next_code);
+            next_code += 1;
+            // the decoder is one code behind, so it only needs the bigger size after the
+            // next code
+            if next_code > 1 << code_size && code_size < 12 {
+                code_size += 1;
+            }
+        } else {
+            // the dictionary is full, start over
+            writer.write(clear_code, code_size);
+            dictionary.clear();
+            next_code = end_code + 1;
+            code_size = min_code_size + 1;
+        }
+
+        prefix = *pixel as u16;
+    }
+
+    writer.write(prefix, code_size);
+    writer.write(end_code, code_size);
+    writer.finish()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::evaluator::LinearEvaluator;
+
+    // decoding the way GIF readers do, to check the encoder against
+    fn lzw_decode(data: &[u8], min_code_size: u8) -> Vec<u8> {
+        let clear_code = 1u16 << min_code_size;
+        let end_code = clear_code + 1;
+        let mut dictionary: Vec<Vec<u8>> = Vec::new();
+        let mut code_size = min_code_size + 1;
+        let mut prev: Option<Vec<u8>> = None;
+        let mut pixels = Vec::new();
+        let (mut buffer, mut bits, mut idx) = (0u32, 0u8, 0usize);
+
+        loop {
+            while bits < code_size {
+                buffer |= (data[idx] as u32) << bits;
+                idx += 1;
+                bits += 8;
+            }
+            let code = (buffer & ((1 << code_size) - 1)) as u16;
+            buffer >>= code_size;
+            bits -= code_size;
+
+            if code == clear_code {
+                dictionary = (0..clear_code).map(|c| vec![c as u8]).collect();
+                dictionary.push(Vec::new());
+                dictionary.push(Vec::new());
+                code_size = min_code_size + 1;
+                prev = None;
+                continue;
+            }
+            if code == end_code {
+                return pixels;
+            }
+
+            let entry = match dictionary.get(code as usize) {
+                Some(entry) => entry.clone(),
+                None => {
+                    let mut entry = prev.clone().unwrap();
+                    entry.push(entry[0]);
+                    entry
+                }
+            };
+            pixels.extend_from_slice(&entry);
+
+            if let Some(mut prev) = prev {
+                prev.push(entry[0]);
+                if dictionary.len() < 4096 {
+                    dictionary.push(prev);
+                }
+                if dictionary.len() >= 1 << code_size && code_size < 12 {
+                    code_size += 1;
+                }
+            }
+            prev = Some(entry);
+        }
+    }
+
+    #[test]
+    fn test_lzw_round_trip() {
+        let mut game = Game::with_seed(1);
+        for _ in 0..3000 {
+            game.update();
+            let action = game.bot(&LinearEvaluator::classic(crate::BEST_WEIGHTS));
+            game.apply_action(action);
+        }
+        let image = Image::from_game(&game);
+        assert_eq!(lzw_decode(&lzw_encode(&image.pixels, 4), 4), image.pixels);
+
+        // enough different sequences to fill up the dictionary
+        let noise: Vec<u8> = (0..100_000u64).map(|i| (i * i / 7 % 16) as u8).collect();
+        assert_eq!(lzw_decode(&lzw_encode(&noise, 4), 4), noise);
+
+        assert!(lzw_decode(&lzw_encode(&[], 4), 4).is_empty());
+    }
+
+    #[test]
+    fn test_png() {
+        let png = encode_png(&Image::from_game(&Game::new()), &Theme::Classic.palette());
+
+        assert_eq!(&png[1..4], b"PNG");
+        assert_eq!(&png[12..16], b"IHDR");
+        assert_eq!(
+            u32::from_be_bytes([png[16], png[17], png[18], png[19]]),
+            160
+        );
+        assert_eq!(
+            u32::from_be_bytes([png[20], png[21], png[22], png[23]]),
+            320
+        );
+        assert_eq!(&png[png.len() - 8..png.len() - 4], b"IEND");
+
+        assert_eq!(crc32(b"IEND"), 0xae42_6082);
+        assert_eq!(adler32(b"Wikipedia"), 0x11e6_0398);
+    }
+}
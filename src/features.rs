@@ -0,0 +1,273 @@
+use crate::tetris::*;
+
+// Board evaluation features used by the classic Tetris bots (Dellacherie, El-Tetris).
+// Every feature is a function over the board, where a non zero value is a block.
+
+fn is_filled(board: &[Vec<u8>], row: usize, col: usize) -> bool {
+    board[row][col] != 0
+}
+
+// Height of every column, measured from the bottom to its highest block
+pub fn column_heights(board: &[Vec<u8>]) -> Vec<u64> {
+    (0..WIDTH as usize)
+        .map(|col| {
+            (0..HEIGHT as usize)
+                .find(|row| is_filled(board, *row, col))
+                .map_or(0, |row| (HEIGHT as usize - row) as u64)
+        })
+        .collect()
+}
+
+// Sum of the column heights
+pub fn aggregate_height(board: &[Vec<u8>]) -> u64 {
+    column_heights(board).iter().sum()
+}
+
+// Number of completely filled rows
+pub fn filled_lines(board: &[Vec<u8>]) -> u64 {
+    board
+        .iter()
+        .filter(|row| row.iter().all(|block| *block != 0))
+        .count() as u64
+}
+
+// Height where the piece was placed: the row of its lowest block counted from the floor
+// (the bottom row is 0), plus the distance from there to the middle of the piece
+pub fn landing_height(piece: &Piece) -> f64 {
+    let blocks = Game::piece_blocks(piece);
+    let top = blocks.iter().map(|(row, _)| *row).min().unwrap();
+    let bottom = blocks.iter().map(|(row, _)| *row).max().unwrap();
+
+    (HEIGHT as i8 - 1 - bottom) as f64 + (bottom - top) as f64 / 2.0
+}
+
+// Number of cleared lines multiplied by the number of blocks of the piece that were
+// cleared with them. The board has to contain the placed piece, before clearing the lines.
+pub fn eroded_piece_cells(board: &[Vec<u8>], piece: &Piece) -> u64 {
+    let filled_rows: Vec<usize> = (0..HEIGHT as usize)
+        .filter(|row| board[*row].iter().all(|block| *block != 0))
+        .collect();
+
+    let eroded_blocks = Game::piece_blocks(piece)
+        .iter()
+        .filter(|(row, _)| filled_rows.contains(&(*row as usize)))
+        .count();
+
+    (filled_rows.len() * eroded_blocks) as u64
+}
+
+// Number of changes between filled and empty blocks along the rows. The walls count as
+// filled blocks.
+pub fn row_transitions(board: &[Vec<u8>]) -> u64 {
+    let mut transitions = 0;
+
+    for row in 0..HEIGHT as usize {
+        let mut prev_filled = true;
+        for col in 0..WIDTH as usize {
+            if is_filled(board, row, col) != prev_filled {
+                transitions += 1;
+            }
+            prev_filled = is_filled(board, row, col);
+        }
+        if !prev_filled {
+            transitions += 1;
+        }
+    }
+
+    transitions
+}
+
+// Number of changes between filled and empty blocks along the columns. The floor counts
+// as filled, the space above the board as empty.
+pub fn column_transitions(board: &[Vec<u8>]) -> u64 {
+    let mut transitions = 0;
+
+    for col in 0..WIDTH as usize {
+        let mut prev_filled = false;
+        for row in 0..HEIGHT as usize {
+            if is_filled(board, row, col) != prev_filled {
+                transitions += 1;
+            }
+            prev_filled = is_filled(board, row, col);
+        }
+        if !prev_filled {
+            transitions += 1;
+        }
+    }
+
+    transitions
+}
+
+// A well is a vertical run of empty blocks with filled blocks (or walls) on both sides.
+// A well of depth N is counted as 1 + 2 + ... + N, so deep wells cost more.
+pub fn cumulative_wells(board: &[Vec<u8>]) -> u64 {
+    let mut wells = 0;
+
+    for col in 0..WIDTH as usize {
+        let mut depth = 0;
+        for row in 0..HEIGHT as usize {
+            let left_filled = col == 0 || is_filled(board, row, col - 1);
+            let right_filled = col == WIDTH as usize - 1 || is_filled(board, row, col + 1);
+
+            if !is_filled(board, row, col) && left_filled && right_filled {
+                depth += 1;
+                wells += depth;
+            } else {
+                depth = 0;
+            }
+        }
+    }
+
+    wells
+}
+
+// Number of empty blocks with at least one filled block above them in their column
+pub fn holes(board: &[Vec<u8>]) -> u64 {
+    let mut holes = 0;
+
+    for col in 0..WIDTH as usize {
+        let mut covered = false;
+        for row in 0..HEIGHT as usize {
+            if is_filled(board, row, col) {
+                covered = true;
+            } else if covered {
+                holes += 1;
+            }
+        }
+    }
+
+    holes
+}
+
+// For every hole, the number of filled blocks above it in its column
+pub fn hole_depth(board: &[Vec<u8>]) -> u64 {
+    let mut depth = 0;
+
+    for col in 0..WIDTH as usize {
+        let mut blocks_above = 0;
+        for row in 0..HEIGHT as usize {
+            if is_filled(board, row, col) {
+                blocks_above += 1;
+            } else {
+                depth += blocks_above;
+            }
+        }
+    }
+
+    depth
+}
+
+// Number of rows with at least one hole
+pub fn rows_with_holes(board: &[Vec<u8>]) -> u64 {
+    let mut rows = vec![false; HEIGHT as usize];
+
+    for col in 0..WIDTH as usize {
+        let mut covered = false;
+        for (row, has_hole) in rows.iter_mut().enumerate() {
+            if is_filled(board, row, col) {
+                covered = true;
+            } else if covered {
+                *has_hole = true;
+            }
+        }
+    }
+
+    rows.iter().filter(|has_hole| **has_hole).count() as u64
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // the board from the given bottom rows, '#' is a block
+    fn board(rows: &[&str]) -> Vec<Vec<u8>> {
+        let mut board = vec![vec![0u8; WIDTH as usize]; HEIGHT as usize];
+        let first_row = HEIGHT as usize - rows.len();
+
+        for (idx, row) in rows.iter().enumerate() {
+            for (col, c) in row.chars().enumerate() {
+                if c == '#' {
+                    board[first_row + idx][col] = 1;
+                }
+            }
+        }
+
+        board
+    }
+
+    #[test]
+    fn test_heights() {
+        let board = board(&["#.........", "#..#......", "##.#.....#"]);
+
+        assert_eq!(column_heights(&board), vec![3, 1, 0, 2, 0, 0, 0, 0, 0, 1]);
+        assert_eq!(aggregate_height(&board), 7);
+    }
+
+    #[test]
+    fn test_landing_height() {
+        // vertical I piece standing on the floor
+        let piece = Piece {
+            kind: 6,
+            rotation: 0,
+            x: HEIGHT as i8 - 4,
+            y: 0,
+        };
+        assert_eq!(landing_height(&piece), 1.5);
+
+        // horizontal I piece on the 3rd row
+        let piece = Piece {
+            kind: 6,
+            rotation: 1,
+            x: HEIGHT as i8 - 4,
+            y: 0,
+        };
+        assert_eq!(landing_height(&piece), 2.0);
+    }
+
+    #[test]
+    fn test_eroded_piece_cells() {
+        // vertical I piece dropped into the well of the last column, completing two lines
+        let mut game = Game::new();
+        game.board = board(&["#########.", "#########."]);
+        game.curr_piece = Piece {
+            kind: 6,
+            rotation: 0,
+            x: HEIGHT as i8 - 4,
+            y: 8,
+        };
+        let piece = game.curr_piece;
+        assert!(game.does_piece_fit(piece.kind, piece.rotation, piece.x, piece.y));
+        assert!(!game.move_piece_down());
+        game.add_current_piece();
+
+        assert_eq!(eroded_piece_cells(&game.board, &game.curr_piece), 4);
+        assert_eq!(eroded_piece_cells(&board(&[]), &game.curr_piece), 0);
+    }
+
+    #[test]
+    fn test_transitions() {
+        let board = board(&["##........", ".#########"]);
+
+        // 2 transitions on the top row, 2 on the bottom one, 2 for every empty row
+        assert_eq!(row_transitions(&board), 2 + 2 + 2 * (HEIGHT as u64 - 2));
+        // 1 for every column, plus 2 around the hole
+        assert_eq!(column_transitions(&board), 10 + 2);
+    }
+
+    #[test]
+    fn test_cumulative_wells() {
+        let board = board(&["#.#......#", "#.#.......", "###......."]);
+
+        // a well of depth 2 in the second column
+        assert_eq!(cumulative_wells(&board), 1 + 2);
+    }
+
+    #[test]
+    fn test_holes() {
+        let board = board(&["##...#....", "#.#..#....", ".##.......", "###..#...."]);
+
+        assert_eq!(holes(&board), 3);
+        assert_eq!(hole_depth(&board), 2 + 1 + 2);
+        assert_eq!(rows_with_holes(&board), 2);
+    }
+}
@@ -0,0 +1,780 @@
+use crate::tetris::*;
+
+// Import and export of fumen (v115) strings, the format the Tetris community uses to share
+// positions. A fumen is a sequence of pages, each page is a field plus an optional piece
+// and comment. The field of a page is stored as the difference from the previous page,
+// after the previous page's piece was locked and the filled lines were cleared.
+
+const FUMEN_PREFIXES: [&str; 3] = ["v115@", "m115@", "d115@"];
+const ENCODE_TABLE: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+const COMMENT_TABLE: &[u8] =
+    b" !\"#$%&'()*+,-./0123456789:;<=>?@ABCDEFGHIJKLMNOPQRSTUVWXYZ[\\]^_`abcdefghijklmnopqrstuvwxyz{|}~";
+
+// The fumen field is 23 rows high plus a garbage row below it. Our board is the bottom 20
+// rows of the field.
+const FIELD_WIDTH: usize = 10;
+const FIELD_TOP: usize = 23;
+const FIELD_ROWS: usize = FIELD_TOP + 1;
+const FIELD_BLOCKS: u32 = (FIELD_ROWS * FIELD_WIDTH) as u32;
+const HIDDEN_ROWS: usize = FIELD_TOP - HEIGHT as usize;
+
+// fumen colours of our piece kinds (S, T, Z, L, O, J, I)
+const FUMEN_COLORS: [u8; 7] = [7, 5, 4, 2, 3, 6, 1];
+// grey garbage blocks have no piece kind, they are imported as I blocks
+const FUMEN_GRAY: u8 = 8;
+
+// fumen rotations
+const REVERSE: u32 = 0;
+const RIGHT: u32 = 1;
+const SPAWN: u32 = 2;
+const LEFT: u32 = 3;
+
+// fumen pieces
+const I: u32 = 1;
+const L: u32 = 2;
+const O: u32 = 3;
+const Z: u32 = 4;
+const T: u32 = 5;
+const J: u32 = 6;
+const S: u32 = 7;
+
+// rows of fumen colours, the first row is the top of the field, the last one is garbage
+type Field = [[u8; FIELD_WIDTH]; FIELD_ROWS];
+
+pub struct FumenPage {
+    pub board: Vec<Vec<u8>>,
+    pub piece: Option<Piece>,
+    // A fumen only stores the comment on the page where it changes, the next pages show it
+    // too until a page sets another one. An empty comment removes it.
+    pub comment: Option<String>,
+}
+
+// A piece in fumen coordinates: x from the left, y from the bottom of the field
+#[derive(Copy, Clone)]
+struct FumenPiece {
+    kind: u32,
+    rotation: u32,
+    x: i32,
+    y: i32,
+}
+
+impl Game {
+    // Exporting the board and the current piece as a single page fumen
+    pub fn to_fumen(&self) -> String {
+        encode_fumen(&[FumenPage {
+            board: self.board.clone(),
+            piece: Some(self.curr_piece),
+            comment: None,
+        }])
+    }
+
+    // Importing the board and the current piece from the first page of a fumen. If the
+    // page has no piece then the default one is kept.
+    pub fn from_fumen(data: &str) -> Result<Game, String> {
+        let page = decode_fumen(data)?
+            .into_iter()
+            .next()
+            .ok_or("empty fumen")?;
+        let mut game = Game::new();
+
+        game.board = page.board;
+        if let Some(piece) = page.piece {
+            game.curr_piece = piece;
+        }
+
+        Ok(game)
+    }
+}
+
+pub fn encode_fumen(pages: &[FumenPage]) -> String {
+    let mut values = Vec::new();
+    let mut prev_field = [[0u8; FIELD_WIDTH]; FIELD_ROWS];
+    // index of the repeat counter of the last unchanged field, if it can be still incremented
+    let mut last_repeat: Option<usize> = None;
+    let mut prev_comment = "";
+
+    for (page_idx, page) in pages.iter().enumerate() {
+        let mut field = [[0u8; FIELD_WIDTH]; FIELD_ROWS];
+        for (row, line) in page.board.iter().enumerate() {
+            for (col, block) in line.iter().enumerate() {
+                if *block != 0 {
+                    field[row + HIDDEN_ROWS][col] = FUMEN_COLORS[*block as usize - 1];
+                }
+            }
+        }
+
+        let field_values = encode_field(&prev_field, &field);
+        match last_repeat {
+            // the field did not change again, so just count it
+            Some(idx) if field == prev_field && values[idx] < 63 => values[idx] += 1,
+            _ => {
+                values.extend(field_values);
+                if field == prev_field {
+                    values.push(0);
+                    last_repeat = Some(values.len() - 1);
+                } else {
+                    last_repeat = None;
+                }
+            }
+        }
+
+        let piece = page.piece.map(to_fumen_piece);
+        let fumen_piece = piece.unwrap_or(FumenPiece {
+            kind: 0,
+            rotation: REVERSE,
+            x: 0,
+            y: FIELD_TOP as i32 - 1,
+        });
+
+        let comment = page.comment.as_deref().unwrap_or("");
+        let comment_changed = comment != prev_comment;
+
+        // flags from the most significant: not locked, comment, colorize, mirror, rise
+        let mut action = 0;
+        action = action * 2 + comment_changed as u32;
+        action = action * 2 + (page_idx == 0) as u32;
+        action *= 4;
+        action = action * FIELD_BLOCKS + encode_coordinate(&fumen_piece);
+        action = action * 4 + fumen_piece.rotation;
+        action = action * 8 + fumen_piece.kind;
+        push_value(&mut values, action, 3);
+
+        if comment_changed {
+            encode_comment(&mut values, comment);
+            prev_comment = comment;
+        }
+
+        // the next page is based on this field with the piece locked
+        if let Some(piece) = piece {
+            put_piece(&mut field, &piece);
+        }
+        clear_lines(&mut field);
+        prev_field = field;
+    }
+
+    let data: String = values
+        .iter()
+        .map(|value| ENCODE_TABLE[*value as usize] as char)
+        .collect();
+
+    // a '?' is inserted in every 47 characters, counting the prefix too
+    let mut fumen = String::from(FUMEN_PREFIXES[0]);
+    for (idx, c) in data.chars().enumerate() {
+        if idx >= 42 && (idx - 42) % 47 == 0 {
+            fumen.push('?');
+        }
+        fumen.push(c);
+    }
+
+    fumen
+}
+
+pub fn decode_fumen(fumen: &str) -> Result<Vec<FumenPage>, String> {
+    let fumen = fumen.trim();
+    let data = FUMEN_PREFIXES
+        .iter()
+        .find_map(|prefix| fumen.strip_prefix(prefix))
+        .ok_or("unsupported fumen version")?;
+
+    let mut values = data
+        .chars()
+        .filter(|c| *c != '?')
+        .map(|c| {
+            ENCODE_TABLE
+                .iter()
+                .position(|e| *e as char == c)
+                .map(|value| value as u32)
+                .ok_or(format!("invalid fumen character '{}'", c))
+        })
+        .collect::<Result<Vec<u32>, String>>()?
+        .into_iter();
+
+    let mut pages = Vec::new();
+    let mut prev_field = [[0u8; FIELD_WIDTH]; FIELD_ROWS];
+    let mut repeat = 0;
+    let mut comment = None;
+
+    while values.len() > 0 {
+        let mut field = prev_field;
+
+        if repeat > 0 {
+            repeat -= 1;
+        } else {
+            let mut idx = 0;
+            while idx < FIELD_BLOCKS {
+                let value = poll_value(&mut values, 2)?;
+                let diff = value / FIELD_BLOCKS;
+                let count = value % FIELD_BLOCKS + 1;
+
+                if idx + count > FIELD_BLOCKS {
+                    return Err("invalid fumen field".to_string());
+                }
+                for block in idx..idx + count {
+                    let cell =
+                        &mut field[block as usize / FIELD_WIDTH][block as usize % FIELD_WIDTH];
+                    let color = *cell as u32 + diff;
+                    if !(8..=16).contains(&color) {
+                        return Err("invalid fumen field".to_string());
+                    }
+                    *cell = (color - 8) as u8;
+                }
+                idx += count;
+
+                // an unchanged field is followed by the number of pages repeating it
+                if diff == 8 && count == FIELD_BLOCKS {
+                    repeat = poll_value(&mut values, 1)?;
+                }
+            }
+        }
+
+        let mut action = poll_value(&mut values, 3)?;
+        let kind = action % 8;
+        action /= 8;
+        let rotation = action % 4;
+        action /= 4;
+        let (x, y) = decode_coordinate(action % FIELD_BLOCKS, kind, rotation);
+        action /= FIELD_BLOCKS;
+        let rise = action % 2 == 1;
+        action /= 2;
+        let mirror = action % 2 == 1;
+        action /= 4; // skipping the colorize flag
+        let has_comment = action % 2 == 1;
+        action /= 2;
+        let lock = action % 2 == 0;
+
+        // without a new comment the page keeps the one of the previous page
+        if has_comment {
+            let text = decode_comment(&mut values)?;
+            comment = Some(text).filter(|text| !text.is_empty());
+        }
+
+        let piece = if kind != 0 {
+            Some(FumenPiece {
+                kind,
+                rotation,
+                x,
+                y,
+            })
+        } else {
+            None
+        };
+
+        pages.push(FumenPage {
+            board: to_board(&field)?,
+            piece: piece.map(to_engine_piece).transpose()?,
+            comment: comment.clone(),
+        });
+
+        // the next page is based on this field with the piece locked
+        if lock {
+            if let Some(piece) = piece {
+                put_piece(&mut field, &piece);
+            }
+            clear_lines(&mut field);
+
+            if rise {
+                for row in 0..FIELD_ROWS - 1 {
+                    field[row] = field[row + 1];
+                }
+                field[FIELD_ROWS - 1] = [0; FIELD_WIDTH];
+            }
+            if mirror {
+                for row in field.iter_mut().take(FIELD_ROWS - 1) {
+                    row.reverse();
+                }
+            }
+        }
+        prev_field = field;
+    }
+
+    Ok(pages)
+}
+
+// Run length encoding of the difference between two fields. Each run is the difference
+// (shifted by 8 to be non-negative) and the run length, from the top left block.
+fn encode_field(prev: &Field, field: &Field) -> Vec<u32> {
+    let mut values = Vec::new();
+    let diff = |block: usize| {
+        let (row, col) = (block / FIELD_WIDTH, block % FIELD_WIDTH);
+        field[row][col] as u32 + 8 - prev[row][col] as u32
+    };
+
+    let mut run_diff = diff(0);
+    let mut run_length = 0;
+    for block in 0..FIELD_BLOCKS as usize {
+        if diff(block) != run_diff {
+            push_value(&mut values, run_diff * FIELD_BLOCKS + run_length - 1, 2);
+            run_diff = diff(block);
+            run_length = 0;
+        }
+        run_length += 1;
+    }
+    push_value(&mut values, run_diff * FIELD_BLOCKS + run_length - 1, 2);
+
+    values
+}
+
+fn put_piece(field: &mut Field, piece: &FumenPiece) {
+    for (x, y) in fumen_piece_blocks(piece).iter() {
+        let row = FIELD_TOP as i32 - 1 - y;
+        if row >= 0 && row < FIELD_ROWS as i32 && *x >= 0 && *x < FIELD_WIDTH as i32 {
+            field[row as usize][*x as usize] = piece.kind as u8;
+        }
+    }
+}
+
+// removing the filled rows of the field above the garbage row
+fn clear_lines(field: &mut Field) {
+    let mut rows: Vec<[u8; FIELD_WIDTH]> = field[..FIELD_ROWS - 1]
+        .iter()
+        .filter(|row| row.contains(&0))
+        .cloned()
+        .collect();
+
+    while rows.len() < FIELD_ROWS - 1 {
+        rows.insert(0, [0; FIELD_WIDTH]);
+    }
+    field[..FIELD_ROWS - 1].copy_from_slice(&rows);
+}
+
+fn to_board(field: &Field) -> Result<Vec<Vec<u8>>, String> {
+    if field[..HIDDEN_ROWS]
+        .iter()
+        .flatten()
+        .any(|block| *block != 0)
+    {
+        return Err("the fumen field is higher than the board".to_string());
+    }
+
+    Ok(field[HIDDEN_ROWS..FIELD_TOP]
+        .iter()
+        .map(|row| {
+            row.iter()
+                .map(|color| match color {
+                    0 => 0,
+                    &FUMEN_GRAY => 7,
+                    _ => FUMEN_COLORS.iter().position(|c| c == color).unwrap() as u8 + 1,
+                })
+                .collect()
+        })
+        .collect())
+}
+
+// The blocks of a fumen piece relative to its center, in spawn rotation
+fn fumen_piece_blocks(piece: &FumenPiece) -> [(i32, i32); 4] {
+    let blocks = match piece.kind {
+        I => [(0, 0), (-1, 0), (1, 0), (2, 0)],
+        L => [(0, 0), (-1, 0), (1, 0), (1, 1)],
+        O => [(0, 0), (1, 0), (0, 1), (1, 1)],
+        Z => [(0, 0), (1, 0), (0, 1), (-1, 1)],
+        T => [(0, 0), (-1, 0), (1, 0), (0, 1)],
+        J => [(0, 0), (-1, 0), (1, 0), (-1, 1)],
+        S => [(0, 0), (-1, 0), (0, 1), (1, 1)],
+        _ => unreachable!("not a fumen piece"),
+    };
+
+    let mut rotated = [(0, 0); 4];
+    for (idx, (x, y)) in blocks.iter().enumerate() {
+        let (x, y) = match piece.rotation {
+            RIGHT => (*y, -*x),
+            REVERSE => (-*x, -*y),
+            LEFT => (-*y, *x),
+            _ => (*x, *y),
+        };
+        rotated[idx] = (piece.x + x, piece.y + y);
+    }
+
+    rotated
+}
+
+// Fumen stores some pieces by a different center than the one used by the rotation system
+fn coordinate_offset(kind: u32, rotation: u32) -> (i32, i32) {
+    match (kind, rotation) {
+        (O, LEFT) => (1, -1),
+        (O, REVERSE) => (1, 0),
+        (O, SPAWN) => (0, -1),
+        (I, REVERSE) => (1, 0),
+        (I, LEFT) => (0, -1),
+        (S, SPAWN) => (0, -1),
+        (S, RIGHT) => (-1, 0),
+        (Z, SPAWN) => (0, -1),
+        (Z, LEFT) => (1, 0),
+        _ => (0, 0),
+    }
+}
+
+fn encode_coordinate(piece: &FumenPiece) -> u32 {
+    if piece.kind == 0 {
+        return 0;
+    }
+
+    let (dx, dy) = coordinate_offset(piece.kind, piece.rotation);
+    let (x, y) = (piece.x - dx, piece.y - dy);
+    ((FIELD_TOP as i32 - y - 1) * FIELD_WIDTH as i32 + x) as u32
+}
+
+fn decode_coordinate(block: u32, kind: u32, rotation: u32) -> (i32, i32) {
+    let (dx, dy) = coordinate_offset(kind, rotation);
+    let x = (block % FIELD_WIDTH as u32) as i32;
+    let y = FIELD_TOP as i32 - (block / FIELD_WIDTH as u32) as i32 - 1;
+    (x + dx, y + dy)
+}
+
+// Normalizing a set of (row, col) blocks to start from (0, 0), returning the offset too
+fn normalize(blocks: &mut [(i32, i32); 4]) -> (i32, i32) {
+    blocks.sort_unstable();
+    let min_row = blocks.iter().map(|b| b.0).min().unwrap();
+    let min_col = blocks.iter().map(|b| b.1).min().unwrap();
+    for block in blocks.iter_mut() {
+        *block = (block.0 - min_row, block.1 - min_col);
+    }
+    (min_row, min_col)
+}
+
+// Finding the engine piece covering the same blocks as the fumen piece
+fn to_engine_piece(piece: FumenPiece) -> Result<Piece, String> {
+    let kind = FUMEN_COLORS
+        .iter()
+        .position(|c| *c as u32 == piece.kind)
+        .unwrap();
+
+    let mut blocks = [(0, 0); 4];
+    for (idx, (x, y)) in fumen_piece_blocks(&piece).iter().enumerate() {
+        blocks[idx] = (HEIGHT as i32 - 1 - y, *x);
+    }
+    if blocks
+        .iter()
+        .any(|(row, col)| *row < 0 || *row >= HEIGHT as i32 || *col < 0 || *col >= WIDTH as i32)
+    {
+        return Err("the fumen piece is outside of the board".to_string());
+    }
+    let (row, col) = normalize(&mut blocks);
+
+    for rotation in 0..4 {
+        let engine_piece = Piece {
+            kind,
+            rotation,
+            x: 0,
+            y: 0,
+        };
+        let mut engine_blocks = [(0, 0); 4];
+        for (idx, (row, col)) in Game::piece_blocks(&engine_piece).iter().enumerate() {
+            engine_blocks[idx] = (*row as i32, *col as i32);
+        }
+        let (engine_row, engine_col) = normalize(&mut engine_blocks);
+
+        if engine_blocks == blocks {
+            return Ok(Piece {
+                kind,
+                rotation,
+                x: (row - engine_row) as i8,
+                y: (col - engine_col) as i8,
+            });
+        }
+    }
+
+    Err("the fumen piece has no matching rotation".to_string())
+}
+
+// Finding the fumen piece covering the same blocks as the engine piece
+fn to_fumen_piece(piece: Piece) -> FumenPiece {
+    let mut blocks = [(0, 0); 4];
+    for (idx, (row, col)) in Game::piece_blocks(&piece).iter().enumerate() {
+        blocks[idx] = (*row as i32, *col as i32);
+    }
+    let (row, col) = normalize(&mut blocks);
+
+    for rotation in [SPAWN, RIGHT, REVERSE, LEFT].iter() {
+        let mut fumen_piece = FumenPiece {
+            kind: FUMEN_COLORS[piece.kind] as u32,
+            rotation: *rotation,
+            x: 0,
+            y: 0,
+        };
+        let mut fumen_blocks = [(0, 0); 4];
+        for (idx, (x, y)) in fumen_piece_blocks(&fumen_piece).iter().enumerate() {
+            fumen_blocks[idx] = (-y, *x);
+        }
+        let (fumen_row, fumen_col) = normalize(&mut fumen_blocks);
+
+        if fumen_blocks == blocks {
+            fumen_piece.x = col - fumen_col;
+            fumen_piece.y = HEIGHT as i32 - 1 - (row - fumen_row);
+            return fumen_piece;
+        }
+    }
+
+    unreachable!("every piece has a fumen rotation")
+}
+
+// Comments are escaped like the javascript escape() does, then every 4 characters are
+// packed into 5 values.
+fn encode_comment(values: &mut Vec<u32>, comment: &str) {
+    let mut escaped = String::new();
+    for c in comment.encode_utf16() {
+        match char::from_u32(c as u32) {
+            Some(c) if c.is_ascii_alphanumeric() || "@*_+-./".contains(c) => escaped.push(c),
+            _ if c < 256 => escaped.push_str(&format!("%{:02X}", c)),
+            _ => escaped.push_str(&format!("%u{:04X}", c)),
+        }
+    }
+    let escaped: Vec<u32> = escaped
+        .bytes()
+        .take(4095)
+        .map(|c| COMMENT_TABLE.iter().position(|e| *e == c).unwrap() as u32)
+        .collect();
+
+    push_value(values, escaped.len() as u32, 2);
+    for chunk in escaped.chunks(4) {
+        let mut value = 0;
+        for c in chunk.iter().rev() {
+            value = value * (COMMENT_TABLE.len() as u32 + 1) + c;
+        }
+        push_value(values, value, 5);
+    }
+}
+
+fn decode_comment(values: &mut impl Iterator<Item = u32>) -> Result<String, String> {
+    let length = poll_value(values, 2)? as usize;
+    let mut escaped = Vec::new();
+
+    for _ in 0..length.div_ceil(4) {
+        let mut value = poll_value(values, 5)?;
+        for _ in 0..4 {
+            let c = value % (COMMENT_TABLE.len() as u32 + 1);
+            escaped.push(
+                *COMMENT_TABLE
+                    .get(c as usize)
+                    .ok_or("invalid fumen comment")?,
+            );
+            value /= COMMENT_TABLE.len() as u32 + 1;
+        }
+    }
+    escaped.truncate(length);
+
+    // undoing the javascript escape()
+    let mut utf16 = Vec::new();
+    let mut idx = 0;
+    while idx < escaped.len() {
+        let hex = |from: usize, len: usize| {
+            escaped
+                .get(from..from + len)
+                .and_then(|digits| std::str::from_utf8(digits).ok())
+                .and_then(|digits| u16::from_str_radix(digits, 16).ok())
+        };
+
+        if escaped[idx] == b'%' && escaped.get(idx + 1) == Some(&b'u') && hex(idx + 2, 4).is_some()
+        {
+            utf16.push(hex(idx + 2, 4).unwrap());
+            idx += 6;
+        } else if escaped[idx] == b'%' && hex(idx + 1, 2).is_some() {
+            utf16.push(hex(idx + 1, 2).unwrap());
+            idx += 3;
+        } else {
+            utf16.push(escaped[idx] as u16);
+            idx += 1;
+        }
+    }
+
+    Ok(String::from_utf16_lossy(&utf16))
+}
+
+// values are stored with the least significant digit first
+fn push_value(values: &mut Vec<u32>, mut value: u32, digits: usize) {
+    for _ in 0..digits {
+        values.push(value % 64);
+        value /= 64;
+    }
+}
+
+fn poll_value(values: &mut impl Iterator<Item = u32>, digits: usize) -> Result<u32, String> {
+    let mut value = 0;
+    let mut multiplier = 1;
+
+    for _ in 0..digits {
+        value += values.next().ok_or("unexpected end of fumen")? * multiplier;
+        multiplier *= 64;
+    }
+
+    Ok(value)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_empty_fumen() {
+        let pages = decode_fumen("v115@vhAAgH").unwrap();
+        assert_eq!(pages.len(), 1);
+        assert!(pages[0].piece.is_none());
+        assert_eq!(pages[0].board, Game::new().board);
+
+        assert_eq!(
+            encode_fumen(&[FumenPage {
+                board: Game::new().board,
+                piece: None,
+                comment: None,
+            }]),
+            "v115@vhAAgH"
+        );
+    }
+
+    // the blocks of the piece of the first page, in board (row, col) coordinates
+    fn decoded_blocks(data: &str) -> [(i8, i8); 4] {
+        let piece = decode_fumen(data).unwrap()[0].piece.unwrap();
+        let mut blocks = Game::piece_blocks(&piece);
+        blocks.sort_unstable();
+        blocks
+    }
+
+    #[test]
+    fn test_vertical_s_z() {
+        // Fumens as the fumen editor writes them: an empty field and a vertical piece
+        // with the lock and colour flags. The piece is stored by the cell fumen uses for
+        // the other vertical orientation of the same shape, one column to the right for
+        // S and to the left for Z.
+        let bottom = HEIGHT as i8 - 1;
+
+        // S, right, stored at x 5 y 1: the blocks x 5 y 0-1 and x 4 y 1-2
+        assert_eq!(
+            decoded_blocks("v115@vhAvLJ"),
+            [
+                (bottom - 2, 4),
+                (bottom - 1, 4),
+                (bottom - 1, 5),
+                (bottom, 5)
+            ]
+        );
+        // Z, left, stored at x 3 y 1: the blocks x 3 y 0-1 and x 4 y 1-2
+        assert_eq!(
+            decoded_blocks("v115@vhA8KJ"),
+            [
+                (bottom - 2, 4),
+                (bottom - 1, 3),
+                (bottom - 1, 4),
+                (bottom, 3)
+            ]
+        );
+
+        // the same Z as a right rotation, stored at x 3 y 1 without any shift
+        assert_eq!(decoded_blocks("v115@vhAsKJ"), decoded_blocks("v115@vhA8KJ"));
+
+        // exported in the first matching rotation, which is the right one for both
+        let export = |data| Game::from_fumen(data).unwrap().to_fumen();
+        assert_eq!(export("v115@vhAvLJ"), "v115@vhAvLJ");
+        assert_eq!(export("v115@vhA8KJ"), "v115@vhAsKJ");
+    }
+
+    #[test]
+    fn test_every_piece_round_trip() {
+        for kind in 0..7 {
+            for rotation in 0..4 {
+                let mut game = Game::new();
+                game.board[HEIGHT as usize - 1] = vec![1, 2, 3, 4, 5, 6, 7, 0, 0, 0];
+                game.curr_piece = Piece {
+                    kind,
+                    rotation,
+                    x: 10,
+                    y: 3,
+                };
+
+                let restored = Game::from_fumen(&game.to_fumen()).unwrap();
+                assert_eq!(restored.board, game.board);
+                assert_eq!(
+                    Game::piece_blocks(&restored.curr_piece),
+                    Game::piece_blocks(&game.curr_piece)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_multiple_pages() {
+        let mut board = Game::new().board;
+        let mut pages = Vec::new();
+        for page in 0..70 {
+            // the board only changes in every 10th page, testing the repeat counter
+            if page % 10 == 0 {
+                board[HEIGHT as usize - 1 - page / 10][page % WIDTH as usize] = 2;
+            }
+            pages.push(FumenPage {
+                board: board.clone(),
+                piece: None,
+                comment: if page == 3 {
+                    Some("100% tetris ready".to_string())
+                } else {
+                    None
+                },
+            });
+        }
+        pages[69].piece = Some(Piece {
+            kind: 6,
+            rotation: 1,
+            x: 5,
+            y: 2,
+        });
+
+        let decoded = decode_fumen(&encode_fumen(&pages)).unwrap();
+        assert_eq!(decoded.len(), pages.len());
+        for (decoded, page) in decoded.iter().zip(pages.iter()) {
+            assert_eq!(decoded.board, page.board);
+            assert_eq!(decoded.comment, page.comment);
+        }
+    }
+
+    #[test]
+    fn test_comments_carry_over() {
+        let page = |comment: Option<&str>| FumenPage {
+            board: Game::new().board,
+            piece: None,
+            comment: comment.map(str::to_string),
+        };
+        let comments = [Some("opener"), None, Some("opener"), Some("opener"), None];
+        let pages: Vec<FumenPage> = comments.iter().map(|comment| page(*comment)).collect();
+
+        let decoded = decode_fumen(&encode_fumen(&pages)).unwrap();
+        for (decoded, comment) in decoded.iter().zip(comments.iter()) {
+            assert_eq!(decoded.comment.as_deref(), *comment);
+        }
+
+        // a repeated comment is only stored once, and the pages without a comment flag show
+        // the comment of the page before them
+        let once = encode_fumen(&[page(Some("opener")), page(Some("opener"))]);
+        let twice = encode_fumen(&[page(Some("opener")), page(Some("second"))]);
+        assert!(once.len() < twice.len());
+        let decoded = decode_fumen(&once).unwrap();
+        assert_eq!(decoded[1].comment.as_deref(), Some("opener"));
+    }
+
+    #[test]
+    fn test_locked_piece_clears_lines() {
+        // an I piece completing the bottom line on the first page
+        let mut board = Game::new().board;
+        board[HEIGHT as usize - 1] = vec![1, 1, 1, 1, 1, 1, 0, 0, 0, 0];
+        let piece = Piece {
+            kind: 6,
+            rotation: 1,
+            x: HEIGHT as i8 - 2,
+            y: 6,
+        };
+
+        let pages = [
+            FumenPage {
+                board,
+                piece: Some(piece),
+                comment: None,
+            },
+            FumenPage {
+                board: Game::new().board,
+                piece: None,
+                comment: None,
+            },
+        ];
+
+        let decoded = decode_fumen(&encode_fumen(&pages)).unwrap();
+        assert_eq!(decoded[1].board, Game::new().board);
+        assert!(decode_fumen("v115@vhAAg").is_err());
+        assert!(decode_fumen("v110@vhAAgH").is_err());
+    }
+}
@@ -0,0 +1,424 @@
+use crate::training::DNA;
+use rand::rngs::StdRng;
+use rand::Rng;
+
+// The operators of the genetic training. Every generation the selection chooses the parents
+// from the population, every child gets the weights of two of them mixed by the crossover,
+// and now and then the mutation changes them.
+
+// Choosing @count parents from the population, which is sorted from the best score down.
+// The same individual can be chosen more than once.
+pub trait Selection {
+    fn select(&self, population: &[DNA], count: usize, rng: &mut StdRng) -> Vec<DNA>;
+}
+
+// The weights of a child of the two parents, the weights stay between 0 and @max_value
+pub trait Crossover {
+    fn crossover(
+        &self,
+        parent1: &[u64],
+        parent2: &[u64],
+        max_value: u64,
+        rng: &mut StdRng,
+    ) -> Vec<u64>;
+}
+
+// Changing the weights of a child, the weights stay between 0 and @max_value
+pub trait Mutation {
+    fn mutate(&self, params: &mut [u64], max_value: u64, rng: &mut StdRng);
+}
+
+// The best @count individuals, with the last 1/12 of the population of them replaced by
+// random ones of the best, so a few of the best get more children
+pub struct Truncation;
+
+impl Selection for Truncation {
+    fn select(&self, population: &[DNA], count: usize, rng: &mut StdRng) -> Vec<DNA> {
+        let mut parents = population[..count].to_vec();
+        let start = count.saturating_sub(population.len() / 12);
+        for parent in parents[start..].iter_mut() {
+            *parent = population[rng.gen::<usize>() % count].clone();
+        }
+        parents
+    }
+}
+
+// Every parent is the best of @size random individuals
+pub struct Tournament {
+    pub size: usize,
+}
+
+impl Selection for Tournament {
+    fn select(&self, population: &[DNA], count: usize, rng: &mut StdRng) -> Vec<DNA> {
+        (0..count)
+            .map(|_| {
+                // the population is sorted, so the smallest index is the best
+                let winner = (0..self.size)
+                    .map(|_| rng.gen_range(0, population.len()))
+                    .min()
+                    .unwrap_or(0);
+                population[winner].clone()
+            })
+            .collect()
+    }
+}
+
+// Every individual is chosen with a chance proportional to its score
+pub struct Roulette;
+
+impl Selection for Roulette {
+    fn select(&self, population: &[DNA], count: usize, rng: &mut StdRng) -> Vec<DNA> {
+        let weights: Vec<u64> = population.iter().map(|dna| dna.score).collect();
+        (0..count)
+            .map(|_| population[spin(&weights, rng)].clone())
+            .collect()
+    }
+}
+
+// Every individual is chosen with a chance proportional to its rank: the best of n
+// individuals gets the weight n, the worst 1. Unlike the roulette, a much better score
+// doesn't let one individual take over the population.
+pub struct Rank;
+
+impl Selection for Rank {
+    fn select(&self, population: &[DNA], count: usize, rng: &mut StdRng) -> Vec<DNA> {
+        let weights: Vec<u64> = (1..=population.len() as u64).rev().collect();
+        (0..count)
+            .map(|_| population[spin(&weights, rng)].clone())
+            .collect()
+    }
+}
+
+// the index of a random weight, with chances proportional to the weights, or uniform when
+// they are all 0
+fn spin(weights: &[u64], rng: &mut StdRng) -> usize {
+    let total: u64 = weights.iter().sum();
+    if total == 0 {
+        return rng.gen_range(0, weights.len());
+    }
+
+    let mut value = rng.gen_range(0, total);
+    for (idx, weight) in weights.iter().enumerate() {
+        if value < *weight {
+            return idx;
+        }
+        value -= weight;
+    }
+    weights.len() - 1
+}
+
+// Every weight comes from either parent with the same chance
+pub struct UniformCrossover;
+
+impl Crossover for UniformCrossover {
+    fn crossover(
+        &self,
+        parent1: &[u64],
+        parent2: &[u64],
+        _max_value: u64,
+        rng: &mut StdRng,
+    ) -> Vec<u64> {
+        parent1
+            .iter()
+            .zip(parent2.iter())
+            .map(|(param1, param2)| {
+                if rng.gen::<usize>() % 2 == 0 {
+                    *param2
+                } else {
+                    *param1
+                }
+            })
+            .collect()
+    }
+}
+
+// The weights before a random point come from the first parent, the rest from the second
+pub struct OnePointCrossover;
+
+impl Crossover for OnePointCrossover {
+    fn crossover(
+        &self,
+        parent1: &[u64],
+        parent2: &[u64],
+        _max_value: u64,
+        rng: &mut StdRng,
+    ) -> Vec<u64> {
+        let point = rng.gen_range(0, parent1.len() + 1);
+        parent1[..point]
+            .iter()
+            .chain(parent2[point..].iter())
+            .copied()
+            .collect()
+    }
+}
+
+// BLX-α: every weight is random between the weights of the parents, in a range widened by
+// @alpha times their distance on both sides, so the children can also leave the range
+// between the parents, but not the range of the weights
+pub struct BlendCrossover {
+    pub alpha: f64,
+}
+
+impl Crossover for BlendCrossover {
+    fn crossover(
+        &self,
+        parent1: &[u64],
+        parent2: &[u64],
+        max_value: u64,
+        rng: &mut StdRng,
+    ) -> Vec<u64> {
+        parent1
+            .iter()
+            .zip(parent2.iter())
+            .map(|(param1, param2)| {
+                let low = *param1.min(param2) as f64;
+                let high = *param1.max(param2) as f64;
+                if low == high {
+                    return *param1;
+                }
+                let extra = self.alpha * (high - low);
+                let max = (max_value - 1) as f64;
+                rng.gen_range(low - extra, high + extra).max(0.0).min(max) as u64
+            })
+            .collect()
+    }
+}
+
+// A random weight changes by a normal distributed value, with a standard deviation of
+// @sigma times @max_value
+pub struct GaussianMutation {
+    pub sigma: f64,
+}
+
+impl Mutation for GaussianMutation {
+    fn mutate(&self, params: &mut [u64], max_value: u64, rng: &mut StdRng) {
+        let param_idx = rng.gen::<usize>() % params.len();
+
+        // the Box-Muller transform of two uniform values
+        let uniform1: f64 = 1.0 - rng.gen::<f64>();
+        let uniform2: f64 = rng.gen();
+        let normal = (-2.0 * uniform1.ln()).sqrt() * (2.0 * std::f64::consts::PI * uniform2).cos();
+
+        let new_val = params[param_idx] as f64 + normal * self.sigma * max_value as f64;
+        params[param_idx] = new_val.max(0.0).min((max_value - 1) as f64) as u64;
+    }
+}
+
+// A random weight is multiplied by a small random factor, a bigger one in 1/3 of the cases.
+// Negative results are left out.
+pub struct ScaledMutation;
+
+impl Mutation for ScaledMutation {
+    fn mutate(&self, params: &mut [u64], _max_value: u64, rng: &mut StdRng) {
+        let param_idx = rng.gen::<usize>() % params.len();
+        let new_val =
+            // do a bigger mutation in 1/3 of the cases
+            if rng.gen::<usize>() % 3 == 0 {
+                params[param_idx] as f64 * rng.gen_range(-0.25, 0.25)
+            }
+            // do a smaller mutation otherwise
+            else {
+                params[param_idx] as f64 * rng.gen_range(-0.05, 0.05)
+            };
+        if new_val >= 0.0f64 {
+            params[param_idx] = new_val as u64;
+        }
+    }
+}
+
+// A random weight gets a new random value, like the weights of a new individual
+pub struct ResetMutation;
+
+impl Mutation for ResetMutation {
+    fn mutate(&self, params: &mut [u64], max_value: u64, rng: &mut StdRng) {
+        let param_idx = rng.gen::<usize>() % params.len();
+        params[param_idx] = rng.gen::<u64>() % max_value;
+    }
+}
+
+// The operators the config of the training can choose, by name
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum SelectionMethod {
+    Truncation,
+    Tournament,
+    Roulette,
+    Rank,
+}
+
+impl SelectionMethod {
+    pub fn name(self) -> &'static str {
+        match self {
+            SelectionMethod::Truncation => "truncation",
+            SelectionMethod::Tournament => "tournament",
+            SelectionMethod::Roulette => "roulette",
+            SelectionMethod::Rank => "rank",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Result<SelectionMethod, String> {
+        match name {
+            "truncation" => Ok(SelectionMethod::Truncation),
+            "tournament" => Ok(SelectionMethod::Tournament),
+            "roulette" => Ok(SelectionMethod::Roulette),
+            "rank" => Ok(SelectionMethod::Rank),
+            _ => Err(format!("unknown selection '{}'", name)),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum CrossoverMethod {
+    Uniform,
+    OnePoint,
+    Blend,
+}
+
+impl CrossoverMethod {
+    pub fn name(self) -> &'static str {
+        match self {
+            CrossoverMethod::Uniform => "uniform",
+            CrossoverMethod::OnePoint => "one_point",
+            CrossoverMethod::Blend => "blend",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Result<CrossoverMethod, String> {
+        match name {
+            "uniform" => Ok(CrossoverMethod::Uniform),
+            "one_point" => Ok(CrossoverMethod::OnePoint),
+            "blend" => Ok(CrossoverMethod::Blend),
+            _ => Err(format!("unknown crossover '{}'", name)),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum MutationMethod {
+    Gaussian,
+    Scaled,
+    Reset,
+}
+
+impl MutationMethod {
+    pub fn name(self) -> &'static str {
+        match self {
+            MutationMethod::Gaussian => "gaussian",
+            MutationMethod::Scaled => "scaled",
+            MutationMethod::Reset => "reset",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Result<MutationMethod, String> {
+        match name {
+            "gaussian" => Ok(MutationMethod::Gaussian),
+            "scaled" => Ok(MutationMethod::Scaled),
+            "reset" => Ok(MutationMethod::Reset),
+            _ => Err(format!("unknown mutation '{}'", name)),
+        }
+    }
+}
+
+// The operators of a training
+pub struct GeneticOperators {
+    pub selection: Box<dyn Selection>,
+    pub crossover: Box<dyn Crossover>,
+    pub mutation: Box<dyn Mutation>,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::SeedableRng;
+
+    fn population(scores: &[u64]) -> Vec<DNA> {
+        scores
+            .iter()
+            .map(|score| DNA {
+                params: vec![*score; 3],
+                score: *score,
+                stats: Default::default(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_selection() {
+        let mut rng = StdRng::seed_from_u64(3);
+        let population = population(&[90, 50, 10, 0]);
+
+        let parents = Truncation.select(&population, 2, &mut rng);
+        assert_eq!(parents, population[..2].to_vec());
+
+        // the worst can't win a tournament of the whole population, nor the roulette
+        let parents = Tournament { size: 10 }.select(&population, 20, &mut rng);
+        assert!(parents.iter().all(|dna| dna.score == 90));
+        let parents = Roulette.select(&population, 50, &mut rng);
+        assert_eq!(parents.len(), 50);
+        assert!(parents.iter().all(|dna| dna.score > 0));
+
+        // the rank gives everyone a chance
+        let parents = Rank.select(&population, 200, &mut rng);
+        for dna in population.iter() {
+            assert!(parents.contains(dna));
+        }
+    }
+
+    #[test]
+    fn test_crossover() {
+        let mut rng = StdRng::seed_from_u64(3);
+        let parent1 = [0, 10, 20, 30, 40, 50];
+        let parent2 = [100, 110, 120, 130, 140, 150];
+
+        let child = UniformCrossover.crossover(&parent1, &parent2, 1000, &mut rng);
+        for (idx, param) in child.iter().enumerate() {
+            assert!(*param == parent1[idx] || *param == parent2[idx]);
+        }
+
+        // a start of the first parent and an end of the second
+        let child = OnePointCrossover.crossover(&parent1, &parent2, 1000, &mut rng);
+        let point = child.iter().take_while(|param| **param < 100).count();
+        assert_eq!(child[..point], parent1[..point]);
+        assert_eq!(child[point..], parent2[point..]);
+
+        // the distance of the parents is 100, the range 50 more on both sides
+        let blend = BlendCrossover { alpha: 0.5 };
+        let child = blend.crossover(&parent1, &parent2, 1000, &mut rng);
+        for (idx, param) in child.iter().enumerate() {
+            assert!(*param + 50 >= parent1[idx] && *param <= parent2[idx] + 50);
+        }
+        assert_eq!(blend.crossover(&parent1, &parent1, 1000, &mut rng), parent1);
+
+        // a wide range is cut off at the range of the weights
+        let blend = BlendCrossover { alpha: 10.0 };
+        for _ in 0..20 {
+            let child = blend.crossover(&parent1, &parent2, 160, &mut rng);
+            assert!(child.iter().all(|param| *param < 160));
+        }
+    }
+
+    #[test]
+    fn test_mutation() {
+        let mut rng = StdRng::seed_from_u64(3);
+        let mutations: [&dyn Mutation; 3] = [
+            &GaussianMutation { sigma: 0.5 },
+            &ScaledMutation,
+            &ResetMutation,
+        ];
+
+        for mutation in mutations.iter() {
+            for _ in 0..20 {
+                let mut params = vec![500; 4];
+                mutation.mutate(&mut params, 1000, &mut rng);
+                // one weight at most changes, and stays in the range
+                assert!(params.iter().filter(|param| **param != 500).count() <= 1);
+                assert!(params.iter().all(|param| *param < 1000));
+            }
+        }
+
+        assert_eq!(
+            MutationMethod::from_name(MutationMethod::Gaussian.name()),
+            Ok(MutationMethod::Gaussian)
+        );
+        assert!(SelectionMethod::from_name("best").is_err());
+    }
+}
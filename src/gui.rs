@@ -0,0 +1,295 @@
+use crate::evaluator::*;
+use crate::replay::*;
+use crate::tetris::MoveAction::*;
+use crate::tetris::*;
+use crate::BEST_WEIGHTS;
+use sdl2::event::Event;
+use sdl2::keyboard::Keycode;
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::{Canvas, TextureCreator};
+use sdl2::ttf::Font;
+use sdl2::video::{Window, WindowContext};
+use sdl2::EventPump;
+use std::cmp;
+use std::time::Duration;
+
+pub fn input(mut event_pump: &mut EventPump) -> MoveAction {
+    let mut action = NONE;
+
+    for event in event_pump.poll_iter() {
+        match event {
+            Event::Quit { .. } => action = QUIT,
+            Event::KeyDown { keycode, .. } => {
+                let keycode = keycode.unwrap();
+                match keycode {
+                    Keycode::Escape => action = QUIT,
+                    Keycode::A | Keycode::Left => action = LEFT,
+                    Keycode::D | Keycode::Right => action = RIGHT,
+                    Keycode::S | Keycode::Down => action = DOWN,
+                    Keycode::W | Keycode::Up | Keycode::Space => action = ROTATE,
+                    _ => (),
+                }
+            }
+            _ => {}
+        }
+    }
+    action
+}
+
+// Drawing the board with the current piece on it, and the score next to it
+fn draw_game(
+    canvas: &mut Canvas<Window>,
+    texture_creator: &TextureCreator<WindowContext>,
+    font: &Font,
+    game: &mut Game,
+) {
+    game.add_current_piece();
+    canvas.set_draw_color(Color::RGB(255, 255, 255));
+    canvas.clear();
+
+    for row in 0..HEIGHT as usize {
+        for col in 0..WIDTH as usize {
+            if game.board[row][col] != 0 {
+                canvas.set_draw_color(Color::RGB(
+                    128,
+                    game.board[row][col] * (255 / 7),
+                    game.board[row][col] * (255 / 7),
+                ));
+            } else {
+                canvas.set_draw_color(Color::RGB(0, 0, 0));
+            }
+
+            canvas
+                .fill_rect(Rect::new(
+                    (col as u32 * RECT_DIM) as i32,
+                    (row as u32 * RECT_DIM) as i32,
+                    RECT_DIM,
+                    RECT_DIM,
+                ))
+                .unwrap();
+        }
+    }
+    draw_text(
+        canvas,
+        texture_creator,
+        font,
+        &format!("Score {}", game.score),
+        0,
+    );
+
+    game.remove_current_piece();
+}
+
+// render a surface, and convert it to a texture bound to the canvas
+fn draw_text(
+    canvas: &mut Canvas<Window>,
+    texture_creator: &TextureCreator<WindowContext>,
+    font: &Font,
+    text: &str,
+    line: i32,
+) {
+    let surface = font
+        .render(text)
+        .blended(Color::RGB(0, 0, 0))
+        .map_err(|e| e.to_string())
+        .unwrap();
+    let texture = texture_creator
+        .create_texture_from_surface(&surface)
+        .map_err(|e| e.to_string())
+        .unwrap();
+    let target = Rect::new(
+        SCREEN_WIDTH as i32 + 20,
+        line * 50,
+        text.len() as u32 * 15,
+        44,
+    );
+    canvas.copy(&texture, None, Some(target)).unwrap();
+}
+
+pub fn run_tetris_with_GUI<E: Evaluator>(run_count: usize, evaluator: &E) -> u32 {
+    // ============
+    // Initializing
+    // ============
+    let sdl_context = sdl2::init().unwrap();
+    let video_subsystem = sdl_context.video().unwrap();
+    let ttf_context = sdl2::ttf::init().map_err(|e| e.to_string()).unwrap();
+
+    let window;
+    let mut canvas;
+    let mut event_pump = sdl_context.event_pump().unwrap();
+    let texture_creator;
+    let mut font;
+
+    window = video_subsystem
+        .window("Tetris", 600, 800)
+        .position_centered()
+        .build()
+        .unwrap();
+
+    canvas = window.into_canvas().build().unwrap();
+    texture_creator = canvas.texture_creator();
+
+    // Load font
+    font = ttf_context.load_font("NotoMono.ttf", 36).unwrap();
+    font.set_style(sdl2::ttf::FontStyle::BOLD);
+
+    canvas.set_draw_color(Color::RGB(255, 255, 255));
+    canvas.clear();
+    canvas.present();
+
+    // ==========
+    // Game logic
+    // ==========
+
+    let mut round_counter = 0;
+    let mut score_accumulator = 0;
+    let mut game = Game::new();
+    let mut replay = Replay::new(0);
+
+    'gameloop: loop {
+        // Update
+        // if the game is over then save its replay and start a new one
+        if !game.update() {
+            round_counter += 1;
+            score_accumulator += game.score;
+
+            replay.save("last.replay").unwrap();
+
+            if round_counter >= run_count {
+                return score_accumulator / run_count as u32;
+            }
+
+            game = Game::with_seed(round_counter as u64);
+            replay = Replay::new(round_counter as u64);
+        }
+
+        let action = game.bot(evaluator);
+
+        // the game stopped in the middle is kept too
+        if input(&mut event_pump) == QUIT {
+            replay.save("last.replay").unwrap();
+            break 'gameloop;
+        }
+
+        game.apply_action(action);
+        replay.actions.push(action);
+
+        // Draw
+        draw_game(&mut canvas, &texture_creator, &font, &mut game);
+        canvas.present();
+
+        ::std::thread::sleep(Duration::new(0, 1_000_000_000u32 / 600));
+    }
+    0
+}
+
+// the available replay speeds, compared to the normal game speed
+const REPLAY_SPEEDS: [f64; 7] = [0.25, 0.5, 1.0, 2.0, 4.0, 8.0, 16.0];
+
+// Playing back a recorded game.
+// Controls:
+//  - Space: pause / resume
+//  - S: step one frame while paused
+//  - Up / Down: faster / slower
+//  - Left / Right: seek to the previous / next piece
+//  - digits then Enter: seek to the given piece number
+//  - F5: save the current position to position.snapshot
+//  - F6: print the current position as a fumen
+pub fn run_replay_with_GUI(replay: Replay, start_piece: u32) {
+    let sdl_context = sdl2::init().unwrap();
+    let video_subsystem = sdl_context.video().unwrap();
+    let ttf_context = sdl2::ttf::init().map_err(|e| e.to_string()).unwrap();
+
+    let window = video_subsystem
+        .window("Tetris replay", 600, 800)
+        .position_centered()
+        .build()
+        .unwrap();
+
+    let mut canvas = window.into_canvas().build().unwrap();
+    let texture_creator = canvas.texture_creator();
+    let mut event_pump = sdl_context.event_pump().unwrap();
+
+    let mut font = ttf_context.load_font("NotoMono.ttf", 36).unwrap();
+    font.set_style(sdl2::ttf::FontStyle::BOLD);
+
+    let mut player = ReplayPlayer::new(replay);
+    player.seek_piece(start_piece);
+
+    let mut paused = false;
+    let mut speed_idx = 2usize;
+    // fractional frames not simulated yet
+    let mut frame_budget = 0f64;
+    let mut piece_input = String::new();
+
+    loop {
+        for event in event_pump.poll_iter() {
+            match event {
+                Event::Quit { .. } => return,
+                Event::KeyDown {
+                    keycode: Some(keycode),
+                    ..
+                } => match keycode {
+                    Keycode::Escape => return,
+                    Keycode::Space => paused = !paused,
+                    Keycode::S if paused => {
+                        player.step();
+                    }
+                    Keycode::Up => speed_idx = cmp::min(speed_idx + 1, REPLAY_SPEEDS.len() - 1),
+                    Keycode::Down => speed_idx = speed_idx.saturating_sub(1),
+                    Keycode::Left => player.seek_piece(player.game.pieces.saturating_sub(1)),
+                    Keycode::Right => player.seek_piece(player.game.pieces + 1),
+                    Keycode::F5 => player.game.save_snapshot("position.snapshot").unwrap(),
+                    Keycode::F6 => println!("{}", player.game.to_fumen()),
+                    Keycode::Return => {
+                        if let Ok(piece) = piece_input.parse::<u32>() {
+                            player.seek_piece(piece);
+                        }
+                        piece_input.clear();
+                    }
+                    _ => {
+                        let name = keycode.name();
+                        if name.len() == 1 && name.chars().all(|c| c.is_ascii_digit()) {
+                            piece_input.push_str(&name);
+                        }
+                    }
+                },
+                _ => {}
+            }
+        }
+
+        if !paused {
+            frame_budget += REPLAY_SPEEDS[speed_idx];
+            while frame_budget >= 1.0 {
+                frame_budget -= 1.0;
+                player.step();
+            }
+        }
+
+        draw_game(&mut canvas, &texture_creator, &font, &mut player.game);
+        let status = if player.finished {
+            "END".to_string()
+        } else if paused {
+            "PAUSED".to_string()
+        } else {
+            format!("x{}", REPLAY_SPEEDS[speed_idx])
+        };
+        let info = [
+            format!("Piece {}", player.game.pieces),
+            format!("Frame {}", player.frame),
+            status,
+            format!("Seek {}", piece_input),
+        ];
+        for (line, text) in info.iter().enumerate() {
+            draw_text(&mut canvas, &texture_creator, &font, text, line as i32 + 1);
+        }
+        canvas.present();
+
+        ::std::thread::sleep(Duration::new(0, 1_000_000_000u32 / 600));
+    }
+}
+
+pub fn main2() {
+    run_tetris_with_GUI(1, &LinearEvaluator::classic(BEST_WEIGHTS));
+}
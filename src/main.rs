@@ -1,321 +1,393 @@
+#[cfg(feature = "gui")]
 extern crate sdl2;
+mod evaluator;
+mod export;
+mod features;
+mod fumen;
+mod genetic;
+#[cfg(feature = "gui")]
+mod gui;
+mod placement;
+mod replay;
+mod search;
+mod simulation;
+mod snapshot;
+mod terminal;
 mod tetris;
+mod training;
+mod transposition;
 
-use rand::{Rng, SeedableRng, thread_rng};
-use sdl2::event::Event;
-use sdl2::keyboard::Keycode;
-use sdl2::pixels::Color;
-use sdl2::rect::Rect;
-use sdl2::render::TextureQuery;
-use sdl2::ttf::Sdl2TtfContext;
-use std::process::exit;
+use rand::Rng;
 use std::time::Duration;
-use chrono::{DateTime, Utc};
+use chrono::Utc;
 
 use rayon::prelude::*;
 
 use crate::tetris::MoveAction::*;
-use sdl2::EventPump;
+use evaluator::*;
+use replay::*;
+use terminal::*;
 use tetris::*;
-use rand::rngs::StdRng;
 use rand::seq::SliceRandom;
-
-pub fn input(mut event_pump: &mut EventPump) -> MoveAction {
-    let mut action = NONE;
-
-    for event in event_pump.poll_iter() {
-        match event {
-            Event::Quit { .. } => action = QUIT,
-            Event::KeyDown { keycode, .. } => {
-                let keycode = keycode.unwrap();
-                match keycode {
-                    Keycode::Escape => action = QUIT,
-                    Keycode::A | Keycode::Left => action = LEFT,
-                    Keycode::D | Keycode::Right => action = RIGHT,
-                    Keycode::S | Keycode::Down => action = DOWN,
-                    Keycode::W | Keycode::Up | Keycode::Space => action = ROTATE,
-                    _ => (),
-                }
-            }
-            _ => {}
-        }
-    }
-    action
-}
-
-pub fn run_tetris_with_GUI(run_count: usize, fitness_params: [u64; 6]) -> u32 {
-    // ============
-    // Initializing
-    // ============
-    let sdl_context = sdl2::init().unwrap();
-    let video_subsystem = sdl_context.video().unwrap();
-    let ttf_context = sdl2::ttf::init().map_err(|e| e.to_string()).unwrap();
-
-    let mut window;
-    let mut canvas;
-    let mut event_pump = sdl_context.event_pump().unwrap();
-    let mut texture_creator;
-    let mut font;
-
-    window = video_subsystem
-        .window("Tetris", 600, 800)
-        .position_centered()
-        .build()
-        .unwrap();
-
-    canvas = window.into_canvas().build().unwrap();
-    texture_creator = canvas.texture_creator();
-
-    // Load font
-    font = ttf_context.load_font("NotoMono.ttf", 36).unwrap();
-    font.set_style(sdl2::ttf::FontStyle::BOLD);
-
-    canvas.set_draw_color(Color::RGB(255, 255, 255));
-    canvas.clear();
-    canvas.present();
-
-    // ==========
-    // Game logic
-    // ==========
-
-    let mut game = Game::new();
-    let mut loop_counter = 0usize;
-    let mut rng = StdRng::from_seed([0; 32]);
+use search::SearchConfig;
+use simulation::*;
+use std::fs;
+use std::io::Write;
+use training::*;
+
+// Watching the bot play in the terminal, for machines where no window can be opened
+pub fn run_tetris_in_terminal<E: Evaluator>(run_count: usize, evaluator: &E) -> u32 {
+    let mut renderer = TerminalRenderer::new();
     let mut round_counter = 0;
     let mut score_accumulator = 0;
+    let mut game = Game::new();
+    let mut frame = 0usize;
 
-    'gameloop: loop {
-        loop_counter += 1;
-        let mut action = NONE;
+    loop {
+        if !game.update() {
+            round_counter += 1;
+            score_accumulator += game.score;
 
-        // Update
-        // force down the piece in every few iteration
-        if loop_counter > 20 {
-            loop_counter = 0;
-            // if cant, then add the piece to the board and spawn a new one
-            if !game.move_piece_down() {
-                game.add_current_piece();
-                let kind = game.next_piece_kind;
+            if round_counter >= run_count {
+                return score_accumulator / run_count as u32;
+            }
 
-                if !game.does_piece_fit(kind, 0, 0, 5) {
-                    round_counter += 1;
-                    score_accumulator += game.score;
+            game = Game::with_seed(round_counter as u64);
+        }
 
-                    game = Game::new();
+        let action = game.bot(evaluator);
+        game.apply_action(action);
 
-                    if round_counter >= run_count {
-                        return score_accumulator / run_count as u32;
-                    }
-                }
+        // terminals can't keep up with the game speed, so draw only every few frames
+        frame += 1;
+        if frame.is_multiple_of(10) {
+            renderer.draw(&game);
+        }
 
-                game.score += 1;
-                game.find_and_remove_solved_lines();
-
-                game.curr_piece = Piece {
-                    kind,
-                    rotation: 0,
-                    x: 0,
-                    y: 5,
-                };
-                game.next_piece_kind = rng.gen::<usize>() % 7;
-                game.target_piece = Piece {
-                    kind: 100,
-                    rotation: 100,
-                    x: 100,
-                    y: 100,
-                };
-            }
+        ::std::thread::sleep(Duration::new(0, 1_000_000_000u32 / 600));
+    }
+}
 
-            // if the current input action was moving down then do not do it since the
-            // piece was already moved down
-            if action == DOWN {
-                action = NONE;
-            }
+// Without a terminal to read the keys from the program can't go on
+fn enable_raw_mode() -> RawMode {
+    match RawMode::enable() {
+        Ok(raw_mode) => raw_mode,
+        Err(e) => {
+            eprintln!("The terminal can't be switched into raw mode: {}", e);
+            std::process::exit(1);
         }
+    }
+}
 
-        let action = game.bot(fitness_params);
-
-        match action {
-            LEFT => game.move_piece_left(),
-            RIGHT => game.move_piece_right(),
-            DOWN => game.move_piece_down(),
-            ROTATE => game.rotate_piece(),
-            QUIT => break 'gameloop,
-            _ => false,
-        };
+// Playing the game in the terminal with the keyboard: arrows or WASD to move and rotate,
+// q or Esc to quit. Quitting saves the game to last.snapshot, which can be continued later.
+pub fn play_in_terminal(mut game: Game) {
+    let raw_mode = enable_raw_mode();
+    let mut renderer = TerminalRenderer::new();
+
+    loop {
+        for action in raw_mode.read_actions() {
+            if action == QUIT {
+                game.save_snapshot("last.snapshot").unwrap();
+                return;
+            }
+            game.apply_action(action);
+        }
 
-        // Draw
+        if !game.update() {
+            renderer.draw_game_over(&game);
 
-        game.add_current_piece();
-        canvas.set_draw_color(Color::RGB(255, 255, 255));
-        canvas.clear();
-
-        for row in 0..HEIGHT as usize {
-            for col in 0..WIDTH as usize {
-                if game.board[row][col] != 0 {
-                    canvas.set_draw_color(Color::RGB(
-                        128,
-                        game.board[row][col] * (255 / 7),
-                        game.board[row][col] * (255 / 7),
-                    ));
-                } else {
-                    canvas.set_draw_color(Color::RGB(0, 0, 0));
+            // wait for a key, then start a new game
+            loop {
+                let actions = raw_mode.read_actions();
+                if actions.contains(&QUIT) {
+                    return;
                 }
-
-                canvas.fill_rect(Rect::new(
-                    (col as u32 * RECT_DIM) as i32,
-                    (row as u32 * RECT_DIM) as i32,
-                    RECT_DIM,
-                    RECT_DIM,
-                ));
+                if !actions.is_empty() {
+                    break;
+                }
+                ::std::thread::sleep(Duration::from_millis(20));
             }
+            game = Game::with_seed(Utc::now().timestamp() as u64);
         }
-        // render a surface, and convert it to a texture bound to the canvas
-        let score = format!("Score {}", game.score);
-        let surface = font
-            .render(&score)
-            .blended(Color::RGB(0, 0, 0))
-            .map_err(|e| e.to_string())
-            .unwrap();
-        let texture = texture_creator
-            .create_texture_from_surface(&surface)
-            .map_err(|e| e.to_string())
-            .unwrap();
-        let target = Rect::new(SCREEN_WIDTH as i32 + 20, 0, score.len() as u32 * 15, 44);
-        canvas.copy(&texture, None, Some(target)).unwrap();
-
-        game.remove_current_piece();
-
-        canvas.present();
 
-        ::std::thread::sleep(Duration::new(0, 1_000_000_000u32 / 600));
+        renderer.draw(&game);
+
+        // the piece falls a row in every 21 frames, about three times a second
+        ::std::thread::sleep(Duration::new(0, 1_000_000_000u32 / 60));
     }
-    0
 }
 
-pub fn run_tetris(run_count: usize, fitness_params: [u64; 6]) -> u32 {
-    // ==========
-    // Game logic
-    // ==========
+// Playing back a recorded game in the terminal.
+// Space pauses, Left / Right seeks to the previous / next piece, q quits.
+#[cfg_attr(feature = "gui", allow(dead_code))]
+pub fn run_replay_in_terminal(replay: Replay, start_piece: u32) {
+    let raw_mode = enable_raw_mode();
+    let mut renderer = TerminalRenderer::new();
+    let mut player = ReplayPlayer::new(replay);
+    let mut paused = false;
+
+    player.seek_piece(start_piece);
+
+    loop {
+        for action in raw_mode.read_actions() {
+            match action {
+                QUIT => return,
+                ROTATE => paused = !paused,
+                LEFT => player.seek_piece(player.game.pieces.saturating_sub(1)),
+                RIGHT => player.seek_piece(player.game.pieces + 1),
+                _ => {}
+            }
+        }
 
-    let mut game = Game::new();
-    let mut loop_counter = 0usize;
-    let mut rng = StdRng::from_seed([0; 32]);
-    let mut round_counter = 0;
-    let mut score_accumulator = 0;
+        // the game runs 10 frames for every drawn one, like in `run_tetris_in_terminal`
+        if !paused {
+            for _ in 0..10 {
+                player.step();
+            }
+        }
+        renderer.draw(&player.game);
 
-    'gameloop: loop {
-        loop_counter += 1;
-        let mut action = NONE;
+        ::std::thread::sleep(Duration::new(0, 1_000_000_000u32 / 60));
+    }
+}
 
-        // Update
-        // force down the piece in every few iteration
-        if loop_counter > 20 {
-            loop_counter = 0;
-            // if cant, then add the piece to the board and spawn a new one
-            if !game.move_piece_down() {
-                game.add_current_piece();
-                let kind = game.next_piece_kind;
+// The bot playing a game without a window for every seed
+pub fn run_tetris<E: Evaluator>(
+    seeds: &[u64],
+    evaluator: &E,
+    simulation: Simulation,
+    limits: &GameLimits,
+) -> Vec<GameResult> {
+    seeds
+        .iter()
+        .map(|seed| Game::with_seed(*seed).simulate(evaluator, simulation, limits))
+        .collect()
+}
 
-                if !game.does_piece_fit(kind, 0, 0, 5) {
-                    round_counter += 1;
-                    score_accumulator += game.score;
+// the trained weights of the classic features, scaled so the largest one is 1
+const BEST_WEIGHTS: [f64; 6] = [1.0, 0.0271876, 1.33012e-05, 4.06889e-06, 0.0139156, 0.0324792];
 
-                    game = Game::new();
+pub fn main() {
+    let args: Vec<String> = std::env::args().collect();
+
+    // tetris replay <file> [piece]
+    if args.len() > 2 && args[1] == "replay" {
+        let replay = Replay::load(&args[2]).unwrap();
+        let start_piece = args.get(3).map_or(0, |piece| piece.parse::<u32>().unwrap());
+        #[cfg(feature = "gui")]
+        gui::run_replay_with_GUI(replay, start_piece);
+        #[cfg(not(feature = "gui"))]
+        run_replay_in_terminal(replay, start_piece);
+        return;
+    }
 
-                    if round_counter >= run_count {
-                        return score_accumulator / run_count as u32;
-                    }
-                }
+    // tetris gui: watching the bot play in a window
+    #[cfg(feature = "gui")]
+    {
+        if args.len() > 1 && args[1] == "gui" {
+            gui::main2();
+            return;
+        }
+    }
 
-                game.score += 1;
-                game.find_and_remove_solved_lines();
-
-                game.curr_piece = Piece {
-                    kind,
-                    rotation: 0,
-                    x: 0,
-                    y: 5,
-                };
-                game.next_piece_kind = rng.gen::<usize>() % 7;
-                game.target_piece = Piece {
-                    kind: 100,
-                    rotation: 100,
-                    x: 100,
-                    y: 100,
-                };
-            }
+    // tetris play [snapshot]: playing in the terminal, optionally continuing a saved game
+    if args.len() > 1 && args[1] == "play" {
+        let game = match args.get(2) {
+            Some(path) => Game::load_snapshot(path).unwrap(),
+            None => Game::with_seed(Utc::now().timestamp() as u64),
+        };
+        play_in_terminal(game);
+        return;
+    }
 
-            // if the current input action was moving down then do not do it since the
-            // piece was already moved down
-            if action == DOWN {
-                action = NONE;
-            }
-        }
+    // tetris watch: the bot playing in the terminal
+    if args.len() > 1 && args[1] == "watch" {
+        let score = run_tetris_in_terminal(1, &LinearEvaluator::classic(BEST_WEIGHTS));
+        println!("Score {}", score);
+        return;
+    }
 
-        let action = game.bot(fitness_params);
+    // tetris record <seed> <max pieces> <replay> [search option...]: recording a bot game
+    // without a window, the search options are given as depth=3, beam=10, time=50...
+    if args.len() > 4 && args[1] == "record" {
+        let seed = args[2].parse::<u64>().unwrap();
+        let max_pieces = args[3].parse::<u32>().unwrap();
+        let config = SearchConfig::from_options(&args[5..]).unwrap();
+        let evaluator = LinearEvaluator::classic(BEST_WEIGHTS);
+        let (replay, stats) = Replay::record_bot(seed, max_pieces, &evaluator, &config);
+        replay.save(&args[4]).unwrap();
+        println!(
+            "{} searches, {:.2} average depth, {} evaluations, {} table lookups, {:.1}% hit rate, \
+             {} replans",
+            stats.searches,
+            stats.average_depth(),
+            stats.evaluations,
+            stats.table_lookups,
+            stats.hit_rate() * 100.0,
+            stats.replans
+        );
+        return;
+    }
 
-        match action {
-            LEFT => game.move_piece_left(),
-            RIGHT => game.move_piece_right(),
-            DOWN => game.move_piece_down(),
-            ROTATE => game.rotate_piece(),
-            QUIT => break 'gameloop,
-            _ => false,
+    // tetris png <snapshot or fumen> <png> [theme]: picture of a position
+    if args.len() > 3 && args[1] == "png" {
+        let game = if args[2].contains('@') {
+            Game::from_fumen(&args[2]).unwrap()
+        } else {
+            Game::load_snapshot(&args[2]).unwrap()
         };
+        let theme = export::Theme::from_name(args.get(4).map_or("classic", |t| t)).unwrap();
+        export::save_png(&game, &theme, &args[3]).unwrap();
+        return;
     }
-    0
-}
 
-const MAX_POSSIBLE_VAL: u64 = 1000_000;
-const POP_SIZE: usize = 100;
-const RUN_AMOUNT: usize = 1;
-const PARENTS_RATIO: usize = 2;
-const PARENTS_SIZE: usize = POP_SIZE / PARENTS_RATIO;
-const TARGET_SCORE: u64 = 1000_000;
-const MAX_GENERATION: u64 = 1000;
-const MUTATION_PROBABILITY: usize = 20;
-
-#[derive(Debug, Eq, Ord, PartialEq, PartialOrd, Copy, Clone)]
-struct DNA {
-    params: [u64; 6],
-    score: u64,
-}
+    // tetris gif <replay> <gif> [theme]: animation of a recorded game
+    if args.len() > 3 && args[1] == "gif" {
+        let replay = Replay::load(&args[2]).unwrap();
+        let theme = export::Theme::from_name(args.get(4).map_or("classic", |t| t)).unwrap();
+        export::save_replay_gif(replay, &theme, &args[3]).unwrap();
+        return;
+    }
 
-impl DNA {
-    pub fn new(max_val: u64) -> Self {
-        let mut rng = rand::thread_rng();
-
-        DNA {
-            params: [
-                rng.gen::<u64>() % max_val,
-                rng.gen::<u64>() % max_val,
-                rng.gen::<u64>() % max_val,
-                rng.gen::<u64>() % max_val,
-                rng.gen::<u64>() % max_val,
-                rng.gen::<u64>() % max_val,
-            ],
-            score: 0,
+    // tetris bot <snapshot or fumen> [feature...]: print the placement the bot chooses in a
+    // position
+    if args.len() > 2 && args[1] == "bot" {
+        let mut game = if args[2].contains('@') {
+            Game::from_fumen(&args[2]).unwrap()
+        } else {
+            Game::load_snapshot(&args[2]).unwrap()
+        };
+        let evaluator = LinearEvaluator::classic(BEST_WEIGHTS);
+        let placements = game.legal_placements();
+        game.bot(&evaluator);
+        // the bot always has a plan after it moved
+        let target = game.plan.as_ref().unwrap().target;
+        println!("{}", game.to_fumen());
+        print!("{}", game.to_ascii());
+        println!(
+            "kind {} rotation {} x {} y {}, out of {} legal placements",
+            target.kind,
+            target.rotation,
+            target.x,
+            target.y,
+            placements.len()
+        );
+        println!(
+            "{}",
+            fumen::encode_fumen(&[fumen::FumenPage {
+                board: game.board.clone(),
+                piece: Some(target),
+                comment: None,
+            }])
+        );
+
+        // the features of the board after the placement, before clearing the lines, with
+        // the weights of the bot. Only the features named after the position are shown.
+        let shown_features = if args.len() > 3 {
+            args[3..]
+                .iter()
+                .map(|name| Feature::from_name(name))
+                .collect::<Result<Vec<Feature>, String>>()
+                .unwrap()
+        } else {
+            Feature::ALL.to_vec()
+        };
+        game.curr_piece = target;
+        game.add_current_piece();
+        for feature in shown_features {
+            let weight = evaluator.weight(feature.name());
+            println!(
+                "{:<20}{:<10}{}",
+                feature.name(),
+                feature.value(&game, &target),
+                weight.map_or("-".to_string(), |weight| weight.to_string())
+            );
         }
+        return;
     }
-}
 
-pub fn main2() {
-    // 285135 [110145] [258489] [787629] [778910] [51634] [973159]
-    run_tetris_with_GUI(1, [33013, 72003, 39630, 12761, 17457, 80641]);
+    // tetris train [config] [--resume]: training the weights of the features, with the
+    // settings of the config file or the default ones. With --resume the training continues
+    // from the checkpoint in the output directory.
+    let train_args = match args.get(1).map(String::as_str) {
+        Some("train") => &args[2..],
+        _ => &[],
+    };
+    let resume = train_args.iter().any(|arg| arg == "--resume");
+    let config = match train_args.iter().find(|arg| *arg != "--resume") {
+        Some(path) => TrainingConfig::load(path).unwrap(),
+        None => TrainingConfig::default(),
+    };
+    let max_param_value = config.max_param_value as f64;
+    let features = config.features.clone();
+    train(&config, resume, |weights| {
+        // the weights of the evaluator are between 0 and 1
+        let weights: Vec<f64> = weights
+            .iter()
+            .map(|weight| *weight as f64 / max_param_value)
+            .collect();
+        LinearEvaluator::new(&features, &weights)
+    })
+    .unwrap();
 }
 
-pub fn main() {
-    let mut population: [DNA; POP_SIZE] = [DNA::new(MAX_POSSIBLE_VAL); POP_SIZE];
-    let mut generation = 0;
-    let mut parents: [DNA; PARENTS_SIZE] = [DNA::new(MAX_POSSIBLE_VAL); PARENTS_SIZE];
-    let mut best = DNA {
-        params: [0u64; 6],
-        score: 0u64,
+// Training the weights of an evaluator, which is built from the weights by make_evaluator.
+// The config, the best individual of every generation and the checkpoints are written to
+// the output directory of the config. Ctrl-C stops the training after writing a checkpoint
+// of the last finished generation, which @resume continues from.
+pub fn train<E, F>(config: &TrainingConfig, resume: bool, make_evaluator: F) -> Result<(), String>
+where
+    E: Evaluator,
+    F: Fn(&[u64]) -> E + Sync,
+{
+    config.check()?;
+    let param_count = config.features.len();
+    let population_size = config.population_size;
+    let parents_size = config.parents_size();
+    let max_param_value = config.max_param_value;
+
+    fs::create_dir_all(&config.output_dir).map_err(|e| e.to_string())?;
+    let results_path = format!("{}/results.txt", config.output_dir);
+    let checkpoint_path = format!("{}/checkpoint.txt", config.output_dir);
+
+    let (mut generation, rng_seed, mut population, mut best) = if resume {
+        let checkpoint = Checkpoint::load(&checkpoint_path)?;
+        checkpoint.check(config)?;
+        println!("Resuming after generation #{}", checkpoint.generation);
+        (
+            checkpoint.generation,
+            checkpoint.rng_seed,
+            checkpoint.population,
+            checkpoint.best,
+        )
+    } else {
+        // the config of a resumed training stays the one it was started with
+        config.save(&format!("{}/config.toml", config.output_dir))?;
+        fs::write(&results_path, "").map_err(|e| e.to_string())?;
+        // every random number of the training comes from the seed of the config
+        let rng_seed = config.seed;
+        let population = (0..population_size)
+            .map(|idx| {
+                let mut rng = individual_rng(rng_seed, 0, idx);
+                DNA::new(&mut rng, max_param_value, param_count)
+            })
+            .collect();
+        let best = DNA {
+            params: vec![0u64; param_count],
+            score: 0u64,
+            stats: SuiteStats::default(),
+        };
+        (0, rng_seed, population, best)
     };
+    let operators = config.operators();
 
-    while best.score < TARGET_SCORE && generation < MAX_GENERATION {
+    catch_interrupt();
+
+    while best.score < config.target_score && generation < config.max_generation {
         generation += 1;
+        // every individual of the generation plays the same games
+        let seeds = suite_seeds(config.seed, generation, config.games);
 
         use std::time::Instant;
         let start = Instant::now();
@@ -330,7 +402,9 @@ pub fn main() {
         //     // }
         // }
 
-        let mut rng = thread_rng();
+        // the state of the generation before anything changed, for the checkpoint
+        let start_population = population.clone();
+        let mut rng = generation_rng(rng_seed, generation);
         population.shuffle(&mut rng);
 
         let mut chunked_populataion: Vec<(usize, &mut [DNA])> =
@@ -339,82 +413,131 @@ pub fn main() {
         chunked_populataion
             .par_iter_mut()
             .for_each(|(i, pop_chunk)| {
-                for idx in 0..10usize {
-                    pop_chunk[idx].score =
-                        run_tetris(RUN_AMOUNT, pop_chunk[idx].params) as u64;
+                for dna in pop_chunk.iter_mut() {
+                    if is_interrupted() {
+                        return;
+                    }
+                    let evaluator = make_evaluator(&dna.params);
+                    let results =
+                        run_tetris(&seeds, &evaluator, config.simulation, &config.limits);
+                    let values: Vec<u64> = results
+                        .iter()
+                        .map(|result| config.objective.value(result))
+                        .collect();
+                    dna.stats = SuiteStats::new(&values);
+                    dna.score = dna.stats.mean as u64;
                 }
             });
 
+        if is_interrupted() {
+            let checkpoint = Checkpoint {
+                generation: generation - 1,
+                rng_seed,
+                settings: config.resume_settings(),
+                best,
+                population: start_population,
+            };
+            checkpoint.save(&checkpoint_path)?;
+            println!(
+                "Interrupted, the training continues from generation #{} with --resume",
+                generation
+            );
+            return Ok(());
+        }
+
         // get the elapsed time
         println!("{:} | Elapsed time {:} ms", Utc::now(), start.elapsed().as_millis());
 
         population.sort_by(|a, b| b.score.cmp(&a.score));
 
+        for (rank, dna) in population.iter().enumerate() {
+            println!(
+                "#{:<3} mean {:<10.1} median {:<10.1} std dev {:.1}",
+                rank, dna.stats.mean, dna.stats.median, dna.stats.std_dev
+            );
+        }
+
         if population[0].score > best.score {
-            best = population[0];
+            best = population[0].clone();
         }
 
         println!(
             "Runs #{}, Generation #{}, Current best score {}",
-            RUN_AMOUNT, generation, best.score
+            config.games, generation, best.score
         );
         for idx in best.params.iter() {
             print!("[{}] ", *idx);
         }
         println!("\n-----------------------------------------------\n");
 
-        // choosing the parents
-        for idx in 0..PARENTS_SIZE {
-            parents[idx] = population[idx];
-        }
+        let mut results = fs::OpenOptions::new()
+            .append(true)
+            .open(&results_path)
+            .map_err(|e| e.to_string())?;
+        writeln!(
+            results,
+            "generation {} best {} mean {:.1} median {:.1} std_dev {:.1} params {:?}",
+            generation,
+            best.score,
+            best.stats.mean,
+            best.stats.median,
+            best.stats.std_dev,
+            best.params
+        )
+        .map_err(|e| e.to_string())?;
 
-        for idx in (PARENTS_SIZE - POP_SIZE / 12)..PARENTS_SIZE {
-            parents[idx] = population[rng.gen::<usize>() % PARENTS_SIZE];
-        }
+        // choosing the parents
+        let parents = operators.selection.select(&population, parents_size, &mut rng);
 
         let mut parent1_idx = 0usize;
         let mut parent2_idx = 0usize;
 
-        // making the next generation population
-        for idx in 0..PARENTS_SIZE {
+        // making the next generation population, every child with its own random numbers
+        for idx in 0..parents_size {
+            let mut rng = individual_rng(rng_seed, generation, idx);
+
             // chose two unique parent
-            parent1_idx = rng.gen::<usize>() % PARENTS_SIZE;
-            parent2_idx = rng.gen::<usize>() % PARENTS_SIZE;
+            parent1_idx = rng.gen::<usize>() % parents_size;
+            parent2_idx = rng.gen::<usize>() % parents_size;
 
             while parent1_idx == parent2_idx {
-                parent2_idx = rng.gen::<usize>() % PARENTS_SIZE;
+                parent2_idx = rng.gen::<usize>() % parents_size;
             }
 
             // crossover
-            population[idx] = parents[parent1_idx];
-            for param_idx in 0..5usize {
-                // 50% chance to crossover the parameter
-                if rng.gen::<usize>() % 2 == 0 {
-                    population[idx].params[param_idx] = parents[parent2_idx].params[param_idx];
-                }
-            }
+            population[idx] = parents[parent1_idx].clone();
+            population[idx].params = operators.crossover.crossover(
+                &parents[parent1_idx].params,
+                &parents[parent2_idx].params,
+                max_param_value,
+                &mut rng,
+            );
 
             // mutation
-            if rng.gen::<usize>() % MUTATION_PROBABILITY == 0 {
-                let param_idx = rng.gen::<usize>() % 6;
-                let new_val =
-                    // do a bigger mutation in 1/3 of the cases
-                    if rng.gen::<usize>() % 3 == 0 {
-                        population[idx].params[param_idx] as f64 * rng.gen_range(-0.25, 0.25)
-                    }
-                    // do a smaller mutation otherwise
-                    else {
-                        population[idx].params[param_idx] as f64 * rng.gen_range(-0.05, 0.05)
-                    };
-                if new_val >= 0.0f64 {
-                    population[idx].params[param_idx] = new_val as u64;
-                }
+            if rng.gen::<usize>() % config.mutation_probability == 0 {
+                operators
+                    .mutation
+                    .mutate(&mut population[idx].params, max_param_value, &mut rng);
             }
+        }
 
-            // choose randomly the rest of the population
-            for idx in PARENTS_SIZE..POP_SIZE {
-                population[idx] = DNA::new(MAX_POSSIBLE_VAL);
-            }
+        // choose randomly the rest of the population
+        for (idx, dna) in population.iter_mut().enumerate().skip(parents_size) {
+            let mut rng = individual_rng(rng_seed, generation, idx);
+            *dna = DNA::new(&mut rng, max_param_value, param_count);
+        }
+
+        if generation % config.checkpoint_interval == 0 {
+            let checkpoint = Checkpoint {
+                generation,
+                rng_seed,
+                settings: config.resume_settings(),
+                best: best.clone(),
+                population: population.clone(),
+            };
+            checkpoint.save(&checkpoint_path)?;
         }
     }
+
+    Ok(())
 }
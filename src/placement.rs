@@ -0,0 +1,373 @@
+use crate::tetris::MoveAction::*;
+use crate::tetris::*;
+use std::collections::HashSet;
+
+// A position where the current piece can be locked
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Placement {
+    pub piece: Piece,
+    // the board cells of the piece, sorted
+    pub cells: [(i8, i8); 4],
+}
+
+impl Placement {
+    pub fn new(piece: Piece) -> Self {
+        let mut cells = Game::piece_blocks(&piece);
+        cells.sort_unstable();
+
+        Placement { piece, cells }
+    }
+}
+
+// A fitting piece sticks out of the board by at most this many blocks of its 4x4 box, so
+// the positions of the search fit into a flat array
+const MARGIN: i8 = 3;
+const ROWS: usize = HEIGHT as usize + 2 * MARGIN as usize;
+const COLS: usize = WIDTH as usize + 2 * MARGIN as usize;
+
+// The search plays the game frame by frame, the way the bot does: one move every frame,
+// then the gravity counter goes up, and when it runs over GRAVITY_FRAMES the piece is pulled
+// down, or locked if it can't move down. Moves which fail are left out, waiting a frame
+// (NONE) is a move of its own.
+//
+// A state of the search is a position with the value of the gravity counter, with the
+// state and the move it was first reached from
+struct State {
+    piece: Piece,
+    gravity_counter: usize,
+    parent: Option<(usize, MoveAction)>,
+}
+
+// A position where the piece locks: from the state, the piece waits pushing down against
+// the blocks below for @waits frames, and locks after the move of the last frame
+struct Lock {
+    piece: Piece,
+    state: usize,
+    waits: usize,
+    action: MoveAction,
+}
+
+// The states the current piece can reach, in breadth first order, and the locks found
+struct Search {
+    states: Vec<State>,
+    locks: Vec<Lock>,
+}
+
+fn position_index(piece: &Piece) -> usize {
+    (piece.rotation as usize * ROWS + (piece.x + MARGIN) as usize) * COLS
+        + (piece.y + MARGIN) as usize
+}
+
+impl Game {
+    // Every placement the current piece can lock into with the moves of the game before
+    // gravity locks it, including tucks and slides under overhangs. Placements covering the
+    // same cells (the rotations of symmetric pieces) are returned only once, in the order
+    // they were found.
+    pub fn legal_placements(&self) -> Vec<Placement> {
+        let mut placements = Vec::new();
+        let mut found_cells = HashSet::new();
+
+        for lock in self.search_positions().locks {
+            let placement = Placement::new(lock.piece);
+            if found_cells.insert(placement.cells) {
+                placements.push(placement);
+            }
+        }
+
+        placements
+    }
+
+    // The moves taking the current piece into the placement, one for every frame until
+    // gravity locks the piece there. None if the placement can't be reached in time.
+    pub fn plan_path(&self, target: &Placement) -> Option<Vec<MoveAction>> {
+        let search = self.search_positions();
+        let lock = search
+            .locks
+            .iter()
+            .find(|lock| Placement::new(lock.piece).cells == target.cells)?;
+
+        let mut path = vec![lock.action];
+        path.extend(std::iter::repeat_n(DOWN, lock.waits));
+        let mut state = &search.states[lock.state];
+        while let Some((parent, action)) = state.parent {
+            path.push(action);
+            state = &search.states[parent];
+        }
+        path.reverse();
+
+        Some(path)
+    }
+
+    fn search_positions(&self) -> Search {
+        let mut search = Search {
+            states: Vec::new(),
+            locks: Vec::new(),
+        };
+
+        if !self.piece_fits(&self.curr_piece) {
+            return search;
+        }
+
+        // The smallest gravity counter every position was reached with. Reaching a position
+        // again with less time left until gravity pulls the piece down is not searched
+        // again, the earlier state can do (nearly) everything that one could.
+        let mut earliest = vec![usize::MAX; 4 * ROWS * COLS];
+        let start = State {
+            piece: self.curr_piece,
+            gravity_counter: self.gravity_counter.min(GRAVITY_FRAMES),
+            parent: None,
+        };
+        earliest[position_index(&start.piece)] = start.gravity_counter;
+        search.states.push(start);
+
+        // the states found so far are the queue of the search
+        let mut idx = 0;
+        while idx < search.states.len() {
+            let piece = search.states[idx].piece;
+            let gravity_counter = search.states[idx].gravity_counter;
+
+            // resting on the blocks, the piece locks here if it waits for gravity
+            if self.is_resting(&piece) {
+                search.locks.push(Lock {
+                    piece,
+                    state: idx,
+                    waits: GRAVITY_FRAMES - gravity_counter,
+                    action: DOWN,
+                });
+            }
+
+            for action in [DOWN, LEFT, RIGHT, ROTATE, NONE].iter() {
+                let mut next = moved_piece(&piece, *action);
+                if *action != NONE && !self.piece_fits(&next) {
+                    continue;
+                }
+
+                let mut next_counter = gravity_counter + 1;
+                if next_counter > GRAVITY_FRAMES {
+                    if self.is_resting(&next) {
+                        search.locks.push(Lock {
+                            piece: next,
+                            state: idx,
+                            waits: 0,
+                            action: *action,
+                        });
+                        continue;
+                    }
+                    next = moved_piece(&next, DOWN);
+                    next_counter = 0;
+                }
+
+                if next_counter < earliest[position_index(&next)] {
+                    earliest[position_index(&next)] = next_counter;
+                    search.states.push(State {
+                        piece: next,
+                        gravity_counter: next_counter,
+                        parent: Some((idx, *action)),
+                    });
+                }
+            }
+
+            idx += 1;
+        }
+
+        search
+    }
+
+    fn piece_fits(&self, piece: &Piece) -> bool {
+        self.does_piece_fit(piece.kind, piece.rotation, piece.x, piece.y)
+    }
+
+    // whether the piece can't move down any further
+    fn is_resting(&self, piece: &Piece) -> bool {
+        !self.does_piece_fit(piece.kind, piece.rotation, piece.x + 1, piece.y)
+    }
+}
+
+// the piece after the move, the same way Game::apply_action moves it
+pub fn moved_piece(piece: &Piece, action: MoveAction) -> Piece {
+    match action {
+        DOWN => Piece {
+            x: piece.x + 1,
+            ..*piece
+        },
+        LEFT => Piece {
+            y: piece.y - 1,
+            ..*piece
+        },
+        RIGHT => Piece {
+            y: piece.y + 1,
+            ..*piece
+        },
+        ROTATE => Piece {
+            rotation: (piece.rotation + 1) % 4,
+            ..*piece
+        },
+        _ => *piece,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn game_with_piece(kind: usize) -> Game {
+        let mut game = Game::new();
+        game.curr_piece = Piece {
+            kind,
+            rotation: 0,
+            x: 0,
+            y: 5,
+        };
+        game
+    }
+
+    #[test]
+    fn test_empty_board_placements() {
+        // O: 9 columns, I: 10 vertical and 7 horizontal, S: 9 vertical and 8 horizontal
+        assert_eq!(game_with_piece(4).legal_placements().len(), 9);
+        assert_eq!(game_with_piece(6).legal_placements().len(), 17);
+        assert_eq!(game_with_piece(0).legal_placements().len(), 17);
+
+        // every placement rests on the floor
+        for placement in game_with_piece(1).legal_placements() {
+            let bottom = placement.cells.iter().map(|(row, _)| *row).max();
+            assert_eq!(bottom, Some(HEIGHT as i8 - 1));
+        }
+    }
+
+    #[test]
+    fn test_slide_under_overhang() {
+        let mut game = game_with_piece(6);
+        for col in 0..5 {
+            game.board[HEIGHT as usize - 2][col] = 1;
+        }
+
+        // the horizontal I piece can slide under the shelf, into the bottom left corner
+        let bottom = HEIGHT as i8 - 1;
+        let tucked = [(bottom, 0), (bottom, 1), (bottom, 2), (bottom, 3)];
+        let placements = game.legal_placements();
+        assert!(placements.iter().any(|placement| placement.cells == tucked));
+
+        // every placement is unique
+        for (idx, placement) in placements.iter().enumerate() {
+            assert!(!placements[idx + 1..]
+                .iter()
+                .any(|other| other.cells == placement.cells));
+        }
+    }
+
+    #[test]
+    fn test_no_placements_when_blocked() {
+        let mut game = game_with_piece(4);
+        game.board[1] = vec![1; WIDTH as usize];
+
+        assert!(game.legal_placements().is_empty());
+    }
+
+    #[test]
+    fn test_plan_path() {
+        let game = game_with_piece(6);
+        let placements = game.legal_placements();
+
+        // following the planned path frame by frame locks the piece in the placement
+        for placement in placements.iter() {
+            let path = game.plan_path(placement).unwrap();
+            assert_eq!(
+                game_after_path(&game, &path).board,
+                board_with(&game, placement)
+            );
+        }
+
+        // the vertical I piece straight down needs only the moves down
+        let path = game.plan_path(&placements[0]).unwrap();
+        assert!(path.iter().all(|action| *action == DOWN));
+    }
+
+    // the moves of the path played with gravity, until the piece locks
+    fn game_after_path(game: &Game, path: &[MoveAction]) -> Game {
+        let mut game = game.clone();
+        for action in path.iter() {
+            assert_eq!(game.pieces, 0);
+            game.apply_action(*action);
+            game.update();
+        }
+        assert_eq!(game.pieces, 1);
+        game
+    }
+
+    fn board_with(game: &Game, placement: &Placement) -> Vec<Vec<u8>> {
+        let mut game = game.clone();
+        game.curr_piece = placement.piece;
+        game.add_current_piece();
+        game.board
+    }
+
+    #[test]
+    fn test_gravity_reachability() {
+        // a shelf on the left, one row above the floor
+        let mut game = game_with_piece(6);
+        for col in 0..5 {
+            game.board[HEIGHT as usize - 2][col] = 1;
+        }
+        let bottom = HEIGHT as i8 - 1;
+        let tucked = Placement::new(Piece {
+            kind: 6,
+            rotation: 1,
+            x: bottom - 1,
+            y: 0,
+        });
+        assert_eq!(
+            tucked.cells,
+            [(bottom, 0), (bottom, 1), (bottom, 2), (bottom, 3)]
+        );
+
+        // right above the floor, gravity locks the piece before it gets there
+        game.curr_piece = Piece {
+            kind: 6,
+            rotation: 1,
+            x: bottom - 2,
+            y: 6,
+        };
+        game.gravity_counter = GRAVITY_FRAMES - 2;
+        assert_eq!(game.plan_path(&tucked), None);
+        assert!(!game.legal_placements().contains(&tucked));
+
+        // with the whole time until gravity the piece makes it
+        game.gravity_counter = 0;
+        let path = game.plan_path(&tucked).unwrap();
+        assert_eq!(
+            game_after_path(&game, &path).board,
+            board_with(&game, &tucked)
+        );
+    }
+
+    #[test]
+    fn test_plan_path_tuck() {
+        let mut game = game_with_piece(6);
+        for col in 0..5 {
+            game.board[HEIGHT as usize - 2][col] = 1;
+        }
+
+        // the tuck needs a move left at the bottom, after the piece has passed the shelf
+        let bottom = HEIGHT as i8 - 1;
+        let tucked = game
+            .legal_placements()
+            .into_iter()
+            .find(|placement| {
+                placement.cells == [(bottom, 0), (bottom, 1), (bottom, 2), (bottom, 3)]
+            })
+            .unwrap();
+        let path = game.plan_path(&tucked).unwrap();
+        let last_move = path.iter().rev().find(|action| **action != DOWN);
+        assert_eq!(last_move, Some(&LEFT));
+
+        // a placement inside the filled blocks can't be reached
+        let unreachable = Placement::new(Piece {
+            kind: 6,
+            rotation: 1,
+            x: HEIGHT as i8 - 3,
+            y: 0,
+        });
+        assert_eq!(game.plan_path(&unreachable), None);
+    }
+}
@@ -0,0 +1,218 @@
+use crate::evaluator::*;
+use crate::search::{SearchConfig, SearchStats};
+use crate::tetris::MoveAction::*;
+use crate::tetris::*;
+use std::fs;
+
+// a snapshot of the game is taken after every this many locked pieces
+const SNAPSHOT_INTERVAL: u32 = 10;
+
+// A recorded game: the seed of the piece sequence and the action taken in every frame.
+pub struct Replay {
+    pub seed: u64,
+    pub actions: Vec<MoveAction>,
+}
+
+impl Replay {
+    pub fn new(seed: u64) -> Self {
+        Replay {
+            seed,
+            actions: Vec::new(),
+        }
+    }
+
+    // Recording the bot playing a game, until it tops out or locks @max_pieces pieces. The
+    // work done by the bot searches is returned with the replay.
+    pub fn record_bot<E: Evaluator>(
+        seed: u64,
+        max_pieces: u32,
+        evaluator: &E,
+        config: &SearchConfig,
+    ) -> (Self, SearchStats) {
+        let mut game = Game::with_seed(seed);
+        let mut replay = Replay::new(seed);
+
+        while game.pieces < max_pieces && game.update() {
+            let action = game.bot_with_config(evaluator, config);
+            game.apply_action(action);
+            replay.actions.push(action);
+        }
+
+        (replay, game.bot_stats)
+    }
+
+    // Saving the replay as a text file. The first line holds the seed, the second one the
+    // actions, one character per frame.
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let actions: String = self
+            .actions
+            .iter()
+            .map(|action| match action {
+                LEFT => 'L',
+                RIGHT => 'R',
+                DOWN => 'D',
+                ROTATE => 'U',
+                _ => '.',
+            })
+            .collect();
+
+        fs::write(path, format!("seed {}\n{}\n", self.seed, actions)).map_err(|e| e.to_string())
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        let content = fs::read_to_string(path).map_err(|e| e.to_string())?;
+        let mut lines = content.lines();
+
+        let seed = lines
+            .next()
+            .and_then(|line| line.strip_prefix("seed "))
+            .ok_or("missing seed")?
+            .trim()
+            .parse::<u64>()
+            .map_err(|e| e.to_string())?;
+
+        let mut replay = Replay::new(seed);
+        for c in lines.next().unwrap_or("").chars() {
+            replay.actions.push(match c {
+                'L' => LEFT,
+                'R' => RIGHT,
+                'D' => DOWN,
+                'U' => ROTATE,
+                '.' => NONE,
+                _ => return Err(format!("invalid action '{}'", c)),
+            });
+        }
+
+        Ok(replay)
+    }
+}
+
+// Re-simulating a recorded game frame by frame. Snapshots are taken periodically while
+// playing, so seeking backwards only needs to re-simulate from the closest snapshot.
+pub struct ReplayPlayer {
+    pub replay: Replay,
+    pub game: Game,
+    pub frame: usize,
+    pub finished: bool,
+    // (frame, game) pairs, ordered by the frame
+    snapshots: Vec<(usize, Game)>,
+}
+
+impl ReplayPlayer {
+    pub fn new(replay: Replay) -> Self {
+        let game = Game::with_seed(replay.seed);
+
+        ReplayPlayer {
+            replay,
+            game: game.clone(),
+            frame: 0,
+            finished: false,
+            snapshots: vec![(0, game)],
+        }
+    }
+
+    // Simulating the next frame. Returns false if the replay has ended.
+    pub fn step(&mut self) -> bool {
+        if self.finished || self.frame >= self.replay.actions.len() {
+            self.finished = true;
+            return false;
+        }
+
+        let pieces = self.game.pieces;
+
+        if !self.game.update() {
+            self.finished = true;
+            return false;
+        }
+        self.game.apply_action(self.replay.actions[self.frame]);
+        self.frame += 1;
+
+        // take a snapshot if a new piece was locked and we don't have one for it yet
+        let last_snapshot_frame = self.snapshots.last().unwrap().0;
+        if self.game.pieces != pieces
+            && self.game.pieces.is_multiple_of(SNAPSHOT_INTERVAL)
+            && self.frame > last_snapshot_frame
+        {
+            self.snapshots.push((self.frame, self.game.clone()));
+        }
+
+        true
+    }
+
+    // Jumping to the first frame where @piece pieces are already locked, or to the end of
+    // the replay if the game has less pieces than that.
+    pub fn seek_piece(&mut self, piece: u32) {
+        if piece < self.game.pieces || self.finished {
+            let (frame, game) = self
+                .snapshots
+                .iter()
+                .rev()
+                .find(|(_, game)| game.pieces <= piece)
+                .unwrap();
+
+            self.frame = *frame;
+            self.game = game.clone();
+            self.finished = false;
+        }
+
+        while self.game.pieces < piece && self.step() {}
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn record(seed: u64, frames: usize) -> Replay {
+        let mut game = Game::with_seed(seed);
+        let mut replay = Replay::new(seed);
+
+        for _ in 0..frames {
+            if !game.update() {
+                break;
+            }
+            let action = game.bot(&LinearEvaluator::classic(crate::BEST_WEIGHTS));
+            game.apply_action(action);
+            replay.actions.push(action);
+        }
+
+        replay
+    }
+
+    #[test]
+    fn test_replay_is_deterministic() {
+        let replay = record(7, 3000);
+        let mut game = Game::with_seed(7);
+        for action in replay.actions.iter() {
+            game.update();
+            game.apply_action(*action);
+        }
+
+        let mut player = ReplayPlayer::new(replay);
+        while player.step() {}
+
+        assert_eq!(player.game.board, game.board);
+        assert_eq!(player.game.pieces, game.pieces);
+    }
+
+    #[test]
+    fn test_seek_piece() {
+        let mut player = ReplayPlayer::new(record(3, 5000));
+        while player.step() {}
+        let last_piece = player.game.pieces;
+        assert!(last_piece > 25);
+
+        player.seek_piece(25);
+        assert_eq!(player.game.pieces, 25);
+        let board = player.game.board.clone();
+
+        // seeking back and forth again should arrive to the same state
+        player.seek_piece(3);
+        assert_eq!(player.game.pieces, 3);
+        player.seek_piece(25);
+        assert_eq!(player.game.board, board);
+
+        player.seek_piece(last_piece + 100);
+        assert_eq!(player.game.pieces, last_piece);
+    }
+}
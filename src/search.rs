@@ -0,0 +1,715 @@
+use crate::evaluator::Evaluator;
+use crate::features;
+use crate::tetris::*;
+use crate::transposition::*;
+use rayon::prelude::*;
+use std::cmp::Ordering;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering as AtomicOrdering};
+use std::time::{Duration, Instant};
+
+// Settings of the placement search of the bot. A deeper search and a wider beam find better
+// placements, but fewer pieces are placed in a second.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SearchConfig {
+    // number of pieces placed in the search, the current one included. The search can't go
+    // deeper than the known pieces: the bot of the game knows the current and the next
+    // piece, so a depth above 2 is searched as 2 there, @chance_depth looks further.
+    pub depth: usize,
+    // number of the best boards kept on every level of the search
+    pub beam_width: usize,
+    // Number of unknown pieces after the known ones, where the search averages over every
+    // kind the piece can be (expectimax). 0 turns it off.
+    pub chance_depth: usize,
+    // Number of evaluations cached in the transposition table of the search, 0 turns it
+    // off. Deeper searches reach the same positions through different placement orders.
+    pub table_size: usize,
+    // Budget of a search in milliseconds and in evaluations, 0 means no limit. With a
+    // budget the search deepens one piece at a time, and when the budget runs out the best
+    // placement of the deepest finished search is used. The first piece is always searched.
+    pub time_budget_ms: u64,
+    pub evaluation_budget: u64,
+}
+
+impl Default for SearchConfig {
+    // the current and the next piece, every placement tried
+    fn default() -> Self {
+        SearchConfig {
+            depth: 2,
+            beam_width: usize::MAX,
+            chance_depth: 0,
+            table_size: 0,
+            time_budget_ms: 0,
+            evaluation_budget: 0,
+        }
+    }
+}
+
+impl SearchConfig {
+    // The default settings changed by "name=value" options, the names are depth, beam,
+    // chance, table, time and evaluations
+    pub fn from_options(options: &[String]) -> Result<SearchConfig, String> {
+        let mut config = SearchConfig::default();
+
+        for option in options {
+            let (name, value) = option
+                .split_once('=')
+                .ok_or(format!("invalid search option '{}'", option))?;
+            let value = value
+                .parse::<u64>()
+                .map_err(|e| format!("invalid search option '{}': {}", option, e))?;
+
+            match name {
+                "depth" => config.depth = value as usize,
+                "beam" => config.beam_width = value as usize,
+                "chance" => config.chance_depth = value as usize,
+                "table" => config.table_size = value as usize,
+                "time" => config.time_budget_ms = value,
+                "evaluations" => config.evaluation_budget = value,
+                _ => return Err(format!("unknown search option '{}'", name)),
+            }
+        }
+
+        config.check()?;
+        Ok(config)
+    }
+
+    // the settings the search can't find a placement with
+    fn check(&self) -> Result<(), String> {
+        if self.depth == 0 || self.beam_width == 0 {
+            return Err("the search depth and the beam width can't be 0".to_string());
+        }
+        Ok(())
+    }
+
+    fn has_budget(&self) -> bool {
+        self.time_budget_ms > 0 || self.evaluation_budget > 0
+    }
+}
+
+// Counters of the work done by the bot: one or more searches, and the planning of the
+// paths to the placements
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct SearchStats {
+    pub searches: u64,
+    // sum of the depths the searches finished, known and unknown pieces together
+    pub total_depth: u64,
+    // number of positions evaluated
+    pub evaluations: u64,
+    pub table_lookups: u64,
+    pub table_hits: u64,
+    // number of times the bot had to plan the path of a piece again
+    pub replans: u64,
+}
+
+impl SearchStats {
+    pub fn add(&mut self, other: &SearchStats) {
+        self.searches += other.searches;
+        self.total_depth += other.total_depth;
+        self.evaluations += other.evaluations;
+        self.table_lookups += other.table_lookups;
+        self.table_hits += other.table_hits;
+        self.replans += other.replans;
+    }
+
+    pub fn average_depth(&self) -> f64 {
+        if self.searches == 0 {
+            return 0.0;
+        }
+        self.total_depth as f64 / self.searches as f64
+    }
+
+    // share of the transposition table lookups which found the evaluation
+    pub fn hit_rate(&self) -> f64 {
+        if self.table_lookups == 0 {
+            return 0.0;
+        }
+        self.table_hits as f64 / self.table_lookups as f64
+    }
+}
+
+// What every part of a search shares, also between the threads
+struct Context<'a, E: Evaluator> {
+    evaluator: &'a E,
+    beam_width: usize,
+    table: Option<SharedTable>,
+    deadline: Option<Instant>,
+    evaluation_budget: u64,
+    // the budget is only checked after the first piece was searched
+    budget_checked: AtomicBool,
+    out_of_budget: AtomicBool,
+    evaluations: AtomicU64,
+    table_lookups: AtomicU64,
+    table_hits: AtomicU64,
+}
+
+impl<'a, E: Evaluator> Context<'a, E> {
+    fn new(evaluator: &'a E, config: &SearchConfig) -> Self {
+        Context {
+            evaluator,
+            beam_width: config.beam_width,
+            table: match config.table_size {
+                0 => None,
+                size => Some(SharedTable::new(size)),
+            },
+            deadline: match config.time_budget_ms {
+                0 => None,
+                ms => Some(Instant::now() + Duration::from_millis(ms)),
+            },
+            evaluation_budget: config.evaluation_budget,
+            budget_checked: AtomicBool::new(false),
+            out_of_budget: AtomicBool::new(false),
+            evaluations: AtomicU64::new(0),
+            table_lookups: AtomicU64::new(0),
+            table_hits: AtomicU64::new(0),
+        }
+    }
+
+    fn is_out_of_budget(&self) -> bool {
+        if !self.budget_checked.load(AtomicOrdering::Relaxed) {
+            return false;
+        }
+        if self.out_of_budget.load(AtomicOrdering::Relaxed) {
+            return true;
+        }
+
+        let evaluations = self.evaluations.load(AtomicOrdering::Relaxed);
+        if (self.evaluation_budget > 0 && evaluations >= self.evaluation_budget)
+            || self
+                .deadline
+                .is_some_and(|deadline| Instant::now() >= deadline)
+        {
+            self.out_of_budget.store(true, AtomicOrdering::Relaxed);
+            return true;
+        }
+
+        false
+    }
+
+    // Evaluating the game with the placed piece, @hash is the hash of the board before the
+    // piece was placed. Out of budget nothing is evaluated anymore, the search is thrown
+    // away anyway.
+    fn evaluate(&self, game: &Game, piece: &Piece, hash: u64) -> f64 {
+        if self.is_out_of_budget() {
+            return f64::NEG_INFINITY;
+        }
+
+        let hash = placement_hash(hash, piece);
+
+        if let Some(table) = &self.table {
+            self.table_lookups.fetch_add(1, AtomicOrdering::Relaxed);
+            if let Some(value) = table.get(hash) {
+                self.table_hits.fetch_add(1, AtomicOrdering::Relaxed);
+                return value;
+            }
+        }
+
+        self.evaluations.fetch_add(1, AtomicOrdering::Relaxed);
+        let value = self.evaluator.evaluate(game, piece);
+
+        if let Some(table) = &self.table {
+            table.insert(hash, value);
+        }
+
+        value
+    }
+
+    fn stats(&self, depth: usize) -> SearchStats {
+        SearchStats {
+            searches: 1,
+            total_depth: depth as u64,
+            evaluations: self.evaluations.load(AtomicOrdering::Relaxed),
+            table_lookups: self.table_lookups.load(AtomicOrdering::Relaxed),
+            table_hits: self.table_hits.load(AtomicOrdering::Relaxed),
+            replans: 0,
+        }
+    }
+}
+
+// A board of the search with its hash, the placement of the current piece it started
+// from, and the sum of the evaluations of the placements which led to it. The first board
+// of the search has no placement yet.
+struct Node {
+    board: Vec<Vec<u8>>,
+    hash: u64,
+    first: Option<Piece>,
+    fitness: f64,
+}
+
+// A placement found on a level of the search, before it is kept or dropped by the beam
+struct Candidate {
+    node_idx: usize,
+    piece: Piece,
+    fitness: f64,
+}
+
+impl Game {
+    // The best placement of the current piece, looking ahead at the given piece kinds: the
+    // first one is the current piece, the rest are the upcoming ones. Every placement is
+    // evaluated with the lines it fills, and they are cleared before the next piece is
+    // placed, so the value of a sequence of placements is the sum of their evaluations.
+    // The placement is None if the current piece can't be placed.
+    //
+    // The game itself is not changed, the search works on copies of the board, so the
+    // placements can be evaluated on several threads.
+    pub fn search_placement<E: Evaluator>(
+        &self,
+        evaluator: &E,
+        queue: &[usize],
+        config: &SearchConfig,
+    ) -> (Option<Piece>, SearchStats) {
+        assert_eq!(config.check(), Ok(()));
+        let ctx = Context::new(evaluator, config);
+        let known = config.depth.min(queue.len());
+        let search = |depth: usize| {
+            // the known pieces come first, then the unknown ones
+            let chance_depth = depth.saturating_sub(known);
+            match chance_depth {
+                0 => self.beam_placement(&ctx, queue, depth),
+                _ => self.expectimax_placement(&ctx, queue, known, chance_depth),
+            }
+        };
+
+        if !config.has_budget() {
+            let depth = known + config.chance_depth;
+            return (search(depth), ctx.stats(depth));
+        }
+
+        // deepening the search while the budget lasts
+        let mut best = search(1);
+        let mut depth = 1;
+        ctx.budget_checked.store(true, AtomicOrdering::Relaxed);
+
+        while depth < known + config.chance_depth && !ctx.is_out_of_budget() {
+            let deeper = search(depth + 1);
+            if ctx.is_out_of_budget() {
+                break;
+            }
+            best = deeper;
+            depth += 1;
+        }
+
+        (best, ctx.stats(depth))
+    }
+
+    fn beam_placement<E: Evaluator>(
+        &self,
+        ctx: &Context<E>,
+        queue: &[usize],
+        depth: usize,
+    ) -> Option<Piece> {
+        let mut scratch = self.clone();
+        let mut nodes = vec![Node {
+            board: self.board.clone(),
+            hash: board_hash(&self.board),
+            first: None,
+            fitness: 0.0,
+        }];
+        let mut best = None;
+
+        for (level, kind) in queue.iter().take(depth).enumerate() {
+            // the placements of the piece on every board of the level
+            let mut moves = Vec::new();
+            for (node_idx, node) in nodes.iter().enumerate() {
+                scratch.board.clone_from(&node.board);
+                match level {
+                    0 => scratch.curr_piece = self.curr_piece,
+                    _ => scratch.spawn(*kind),
+                }
+                for placement in scratch.legal_placements() {
+                    moves.push((node_idx, placement.piece));
+                }
+            }
+
+            // no placement on this level, the best one of the previous level has to do
+            if moves.is_empty() || ctx.is_out_of_budget() {
+                break;
+            }
+
+            let mut candidates: Vec<Candidate> = moves
+                .par_iter()
+                .map_init(
+                    || self.clone(),
+                    |scratch, (node_idx, piece)| {
+                        let node = &nodes[*node_idx];
+                        scratch.board.clone_from(&node.board);
+                        scratch.curr_piece = *piece;
+                        scratch.add_current_piece();
+                        Candidate {
+                            node_idx: *node_idx,
+                            piece: *piece,
+                            fitness: node.fitness + ctx.evaluate(scratch, piece, node.hash),
+                        }
+                    },
+                )
+                .collect();
+
+            // the best first, keeping the order of the placements with the same fitness
+            candidates.sort_by(|a, b| b.fitness.partial_cmp(&a.fitness).unwrap_or(Ordering::Equal));
+            candidates.truncate(ctx.beam_width);
+
+            let first_of =
+                |candidate: &Candidate| nodes[candidate.node_idx].first.unwrap_or(candidate.piece);
+            best = Some(first_of(&candidates[0]));
+
+            // the boards of the next level, not needed after the last one
+            if level + 1 < depth.min(queue.len()) {
+                nodes = candidates
+                    .iter()
+                    .map(|candidate| {
+                        let node = &nodes[candidate.node_idx];
+                        scratch.board.clone_from(&node.board);
+                        scratch.curr_piece = candidate.piece;
+                        scratch.add_current_piece();
+                        let mut board = scratch.board.clone();
+                        let hash = match clear_filled_rows(&mut board) {
+                            true => board_hash(&board),
+                            false => with_piece_blocks(node.hash, &candidate.piece),
+                        };
+                        Node {
+                            board,
+                            hash,
+                            first: Some(first_of(candidate)),
+                            fitness: candidate.fitness,
+                        }
+                    })
+                    .collect();
+            }
+        }
+
+        best
+    }
+
+    // Expectimax search: the known pieces are placed one after the other, then every kind
+    // of the next unknown piece is tried and the best values are averaged over the
+    // kinds. The randomizer draws every kind with the same chance. Only the best
+    // @beam_width placements of every piece are looked at more deeply. The placements of
+    // the current piece are searched on several threads.
+    fn expectimax_placement<E: Evaluator>(
+        &self,
+        ctx: &Context<E>,
+        queue: &[usize],
+        depth: usize,
+        chance_depth: usize,
+    ) -> Option<Piece> {
+        let rest = queue.get(1..depth).unwrap_or(&[]);
+        let hash = board_hash(&self.board);
+
+        let candidates = self.clone().ranked_placements(ctx, hash);
+        let values: Vec<f64> = candidates
+            .par_iter()
+            .map_init(
+                || self.clone(),
+                |scratch, (piece, evaluation)| {
+                    evaluation + scratch.placement_value(ctx, *piece, hash, rest, chance_depth)
+                },
+            )
+            .collect();
+
+        best_of(&candidates, &values).map(|(piece, _)| piece)
+    }
+
+    // The best placement of the piece with its value. The piece is the current one if
+    // @kind is None. None if the piece can't be placed.
+    fn max_node<E: Evaluator>(
+        &mut self,
+        ctx: &Context<E>,
+        kind: Option<usize>,
+        hash: u64,
+        rest: &[usize],
+        chance_depth: usize,
+    ) -> Option<(Piece, f64)> {
+        if ctx.is_out_of_budget() {
+            return None;
+        }
+        if let Some(kind) = kind {
+            self.spawn(kind);
+        }
+
+        let candidates = self.ranked_placements(ctx, hash);
+        if rest.is_empty() && chance_depth == 0 {
+            return candidates.first().copied();
+        }
+
+        let values: Vec<f64> = candidates
+            .iter()
+            .map(|(piece, evaluation)| {
+                evaluation + self.placement_value(ctx, *piece, hash, rest, chance_depth)
+            })
+            .collect();
+
+        best_of(&candidates, &values)
+    }
+
+    // The placements of the current piece with their evaluation, the best first. Only the
+    // best @beam_width ones are kept.
+    fn ranked_placements<E: Evaluator>(
+        &mut self,
+        ctx: &Context<E>,
+        hash: u64,
+    ) -> Vec<(Piece, f64)> {
+        let mut candidates = Vec::new();
+
+        for placement in self.legal_placements() {
+            self.curr_piece = placement.piece;
+            self.add_current_piece();
+            candidates.push((placement.piece, ctx.evaluate(self, &placement.piece, hash)));
+            self.remove_current_piece();
+        }
+        candidates.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(Ordering::Equal));
+        candidates.truncate(ctx.beam_width);
+
+        candidates
+    }
+
+    // The value of the board after the placement, without the evaluation of the placement
+    // itself: the best of the next known piece, or the average over the unknown ones
+    fn placement_value<E: Evaluator>(
+        &mut self,
+        ctx: &Context<E>,
+        piece: Piece,
+        hash: u64,
+        rest: &[usize],
+        chance_depth: usize,
+    ) -> f64 {
+        self.curr_piece = piece;
+        self.add_current_piece();
+        let mut hash = with_piece_blocks(hash, &piece);
+
+        // the board with the filled lines is restored afterwards
+        let mut filled_board = None;
+        if features::filled_lines(&self.board) > 0 {
+            filled_board = Some(self.board.clone());
+            clear_filled_rows(&mut self.board);
+            hash = board_hash(&self.board);
+        }
+
+        let value = match rest.split_first() {
+            Some((next, rest)) => self
+                .max_node(ctx, Some(*next), hash, rest, chance_depth)
+                .map_or(f64::NEG_INFINITY, |(_, value)| value),
+            None => self.chance_node(ctx, hash, chance_depth),
+        };
+
+        if let Some(board) = filled_board {
+            self.board = board;
+        }
+        self.curr_piece = piece;
+        self.remove_current_piece();
+
+        value
+    }
+
+    // The average of the best values over the kinds of the next piece. A kind which can't
+    // be placed ends the game, so it counts as the worst value possible.
+    fn chance_node<E: Evaluator>(
+        &mut self,
+        ctx: &Context<E>,
+        hash: u64,
+        chance_depth: usize,
+    ) -> f64 {
+        let total: f64 = (0..7)
+            .map(|kind| {
+                self.max_node(ctx, Some(kind), hash, &[], chance_depth - 1)
+                    .map_or(f64::NEG_INFINITY, |(_, value)| value)
+            })
+            .sum();
+
+        total / 7.0
+    }
+
+    // the next piece enters the board, with the whole time until gravity pulls it down
+    fn spawn(&mut self, kind: usize) {
+        self.curr_piece = spawned_piece(kind);
+        self.gravity_counter = 0;
+    }
+}
+
+// Removing the filled rows of the board, the rows above them move down. Returns whether a
+// row was removed.
+fn clear_filled_rows(board: &mut Vec<Vec<u8>>) -> bool {
+    let rows = board.len();
+    board.retain(|row| row.contains(&0));
+    let cleared = rows - board.len();
+    for _ in 0..cleared {
+        board.insert(0, vec![0; WIDTH as usize]);
+    }
+
+    cleared > 0
+}
+
+fn spawned_piece(kind: usize) -> Piece {
+    Piece {
+        kind,
+        rotation: 0,
+        x: 0,
+        y: 5,
+    }
+}
+
+// the first placement with the highest value, with the value
+fn best_of(candidates: &[(Piece, f64)], values: &[f64]) -> Option<(Piece, f64)> {
+    let mut best: Option<(Piece, f64)> = None;
+
+    for ((piece, _), value) in candidates.iter().zip(values.iter()) {
+        if best.is_none_or(|(_, best_value)| *value > best_value) {
+            best = Some((*piece, *value));
+        }
+    }
+
+    best
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::evaluator::LinearEvaluator;
+
+    #[test]
+    fn test_search_depth() {
+        let mut game = Game::with_seed(3);
+        game.curr_piece = spawned_piece(6);
+        let evaluator = LinearEvaluator::classic(crate::BEST_WEIGHTS);
+
+        for depth in 1..4 {
+            let config = SearchConfig {
+                depth,
+                beam_width: 5,
+                ..SearchConfig::default()
+            };
+            let (best, _) = game.search_placement(&evaluator, &[6, 4, 1], &config);
+            assert_eq!(best.map(|piece| piece.kind), Some(6));
+        }
+
+        // a beam as wide as the number of placements finds the same as the full search
+        let (full, _) = game.search_placement(&evaluator, &[6, 4], &SearchConfig::default());
+        let config = SearchConfig {
+            beam_width: 17,
+            ..SearchConfig::default()
+        };
+        assert_eq!(game.search_placement(&evaluator, &[6, 4], &config).0, full);
+    }
+
+    #[test]
+    fn test_search_blocked() {
+        let mut game = Game::new();
+        game.board[1] = vec![1; WIDTH as usize];
+
+        let evaluator = LinearEvaluator::classic(crate::BEST_WEIGHTS);
+        let (best, _) = game.search_placement(&evaluator, &[0, 0], &SearchConfig::default());
+        assert_eq!(best, None);
+    }
+
+    #[test]
+    fn test_expectimax() {
+        let mut game = Game::with_seed(3);
+        game.board[HEIGHT as usize - 1] = vec![1, 1, 1, 1, 1, 1, 0, 0, 1, 1];
+        game.curr_piece = spawned_piece(4);
+        let evaluator = LinearEvaluator::classic(crate::BEST_WEIGHTS);
+
+        // without any preview, the O piece fills the gap and the line
+        let config = SearchConfig {
+            depth: 1,
+            beam_width: 4,
+            chance_depth: 1,
+            ..SearchConfig::default()
+        };
+        let best = game.search_placement(&evaluator, &[4], &config).0.unwrap();
+        assert_eq!(best.y, 5);
+        assert_eq!(best.x, HEIGHT as i8 - 3);
+
+        game.board[1] = vec![1; WIDTH as usize];
+        assert_eq!(game.search_placement(&evaluator, &[4], &config).0, None);
+    }
+
+    #[test]
+    fn test_clear_filled_rows() {
+        let mut board = vec![vec![0; WIDTH as usize]; HEIGHT as usize];
+        board[HEIGHT as usize - 1] = vec![1; WIDTH as usize];
+        board[HEIGHT as usize - 2] = vec![1, 1, 1, 1, 1, 1, 0, 0, 1, 1];
+        board[HEIGHT as usize - 3] = vec![1; WIDTH as usize];
+        let mut expected = vec![vec![0; WIDTH as usize]; HEIGHT as usize];
+        expected[HEIGHT as usize - 1] = board[HEIGHT as usize - 2].clone();
+
+        assert!(clear_filled_rows(&mut board));
+        assert_eq!(board, expected);
+        assert!(!clear_filled_rows(&mut board));
+        assert_eq!(board, expected);
+    }
+
+    #[test]
+    fn test_transposition_table() {
+        let mut game = Game::new();
+        game.curr_piece = spawned_piece(4);
+        let evaluator = LinearEvaluator::classic(crate::BEST_WEIGHTS);
+        let config = SearchConfig {
+            depth: 3,
+            beam_width: 50,
+            ..SearchConfig::default()
+        };
+        let (best, stats) = game.search_placement(&evaluator, &[4, 4, 4], &config);
+        assert_eq!(stats.table_lookups, 0);
+
+        // the same O pieces placed in a different order give the same boards
+        let cached = SearchConfig {
+            table_size: 1 << 16,
+            ..config
+        };
+        let (cached_best, cached_stats) = game.search_placement(&evaluator, &[4, 4, 4], &cached);
+        assert_eq!(cached_best, best);
+        assert!(cached_stats.table_hits > 0);
+        assert_eq!(
+            cached_stats.evaluations + cached_stats.table_hits,
+            stats.evaluations
+        );
+    }
+
+    #[test]
+    fn test_budget() {
+        let mut game = Game::with_seed(3);
+        game.curr_piece = spawned_piece(2);
+        let evaluator = LinearEvaluator::classic(crate::BEST_WEIGHTS);
+        let config = SearchConfig {
+            chance_depth: 1,
+            beam_width: 3,
+            ..SearchConfig::default()
+        };
+
+        // out of budget after the first piece, the result of the one piece search is used
+        let budget = SearchConfig {
+            evaluation_budget: 1,
+            ..config
+        };
+        let (best, stats) = game.search_placement(&evaluator, &[2, 5], &budget);
+        let shallow = SearchConfig {
+            depth: 1,
+            chance_depth: 0,
+            ..config
+        };
+        assert_eq!(best, game.search_placement(&evaluator, &[2, 5], &shallow).0);
+        assert_eq!(stats.average_depth(), 1.0);
+
+        // with enough budget every piece is searched
+        let budget = SearchConfig {
+            time_budget_ms: 60_000,
+            ..config
+        };
+        let (best, stats) = game.search_placement(&evaluator, &[2, 5], &budget);
+        assert_eq!(best, game.search_placement(&evaluator, &[2, 5], &config).0);
+        assert_eq!(stats.average_depth(), 3.0);
+    }
+
+    #[test]
+    fn test_options() {
+        let options: Vec<String> = vec!["depth=3".to_string(), "time=20".to_string()];
+        let config = SearchConfig::from_options(&options).unwrap();
+        assert_eq!(config.depth, 3);
+        assert_eq!(config.time_budget_ms, 20);
+        assert_eq!(config.beam_width, SearchConfig::default().beam_width);
+
+        assert!(SearchConfig::from_options(&["depth".to_string()]).is_err());
+        assert!(SearchConfig::from_options(&["speed=3".to_string()]).is_err());
+        assert!(SearchConfig::from_options(&["depth=x".to_string()]).is_err());
+        assert!(SearchConfig::from_options(&["depth=0".to_string()]).is_err());
+        assert!(SearchConfig::from_options(&["beam=0".to_string()]).is_err());
+    }
+}
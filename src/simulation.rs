@@ -0,0 +1,255 @@
+use crate::evaluator::Evaluator;
+use crate::features::column_heights;
+use crate::search::SearchConfig;
+use crate::tetris::*;
+use std::time::{Duration, Instant};
+
+// How the games without a window are played
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Simulation {
+    // frame by frame like a shown game: the bot makes a move in every frame and gravity
+    // pulls the piece down
+    Frames,
+    // only the placements: the piece is locked at once where the bot would take it, the
+    // frames in between are skipped
+    Placements,
+}
+
+impl Simulation {
+    pub fn name(self) -> &'static str {
+        match self {
+            Simulation::Frames => "frames",
+            Simulation::Placements => "placements",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Result<Simulation, String> {
+        match name {
+            "frames" => Ok(Simulation::Frames),
+            "placements" => Ok(Simulation::Placements),
+            _ => Err(format!("unknown simulation '{}'", name)),
+        }
+    }
+}
+
+// The game stops at the first limit it reaches, 0 means no limit. With good weights the
+// bot can play for ever, so the games of the training need some limit.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct GameLimits {
+    pub pieces: u32,
+    pub lines: u32,
+    pub time_ms: u64,
+}
+
+// How a game without a window went
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct GameResult {
+    pub lines: u32,
+    pub pieces: u32,
+    pub score: u32,
+    // the highest the blocks were stacked after a piece was locked
+    pub max_height: u32,
+    // whether the game was over, or it was stopped by a limit
+    pub topped_out: bool,
+}
+
+// What the training maximises
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Objective {
+    Lines,
+    Pieces,
+    Score,
+}
+
+impl Objective {
+    pub fn name(self) -> &'static str {
+        match self {
+            Objective::Lines => "lines",
+            Objective::Pieces => "pieces",
+            Objective::Score => "score",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Result<Objective, String> {
+        match name {
+            "lines" => Ok(Objective::Lines),
+            "pieces" => Ok(Objective::Pieces),
+            "score" => Ok(Objective::Score),
+            _ => Err(format!("unknown objective '{}'", name)),
+        }
+    }
+
+    pub fn value(self, result: &GameResult) -> u64 {
+        match self {
+            Objective::Lines => result.lines as u64,
+            Objective::Pieces => result.pieces as u64,
+            Objective::Score => result.score as u64,
+        }
+    }
+}
+
+// The spread of the objective over the games of an individual
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct SuiteStats {
+    pub mean: f64,
+    pub median: f64,
+    pub std_dev: f64,
+}
+
+impl SuiteStats {
+    pub fn new(values: &[u64]) -> Self {
+        if values.is_empty() {
+            return SuiteStats::default();
+        }
+
+        let count = values.len() as f64;
+        let mean = values.iter().sum::<u64>() as f64 / count;
+        let variance = values
+            .iter()
+            .map(|value| (*value as f64 - mean).powi(2))
+            .sum::<f64>()
+            / count;
+
+        let mut sorted = values.to_vec();
+        sorted.sort_unstable();
+        let middle = sorted.len() / 2;
+        let median = match sorted.len() % 2 {
+            0 => (sorted[middle - 1] + sorted[middle]) as f64 / 2.0,
+            _ => sorted[middle] as f64,
+        };
+
+        SuiteStats {
+            mean,
+            median,
+            std_dev: variance.sqrt(),
+        }
+    }
+}
+
+// The seeds of the games every individual of the generation plays. The individuals of a
+// generation play the same games (common random numbers), so the differences of their
+// results come from their weights, not from the luck of the pieces. Every generation gets
+// new games, so the weights can't fit a few piece sequences. The seeds follow each other
+// from @seed on.
+pub fn suite_seeds(seed: u64, generation: u64, size: usize) -> Vec<u64> {
+    (0..size as u64)
+        .map(|idx| seed.wrapping_add(generation * size as u64 + idx))
+        .collect()
+}
+
+impl GameLimits {
+    fn is_reached(&self, game: &Game, start: Instant) -> bool {
+        (self.pieces > 0 && game.pieces >= self.pieces)
+            || (self.lines > 0 && game.lines >= self.lines)
+            || (self.time_ms > 0 && start.elapsed() >= Duration::from_millis(self.time_ms))
+    }
+}
+
+impl Game {
+    // The bot playing the game until it is over or reaches one of the limits
+    pub fn simulate<E: Evaluator>(
+        &mut self,
+        evaluator: &E,
+        simulation: Simulation,
+        limits: &GameLimits,
+    ) -> GameResult {
+        let start = Instant::now();
+        let mut max_height = 0;
+        let mut topped_out = false;
+
+        while !limits.is_reached(self, start) {
+            let pieces = self.pieces;
+            let running = match simulation {
+                Simulation::Frames => {
+                    let running = self.update();
+                    if running {
+                        let action = self.bot(evaluator);
+                        self.apply_action(action);
+                    }
+                    running
+                }
+                Simulation::Placements => self.bot_place_piece(evaluator, &SearchConfig::default()),
+            };
+
+            // the last piece is on the board even when the next one doesn't fit anymore
+            if !running || self.pieces != pieces {
+                max_height = max_height.max(stack_height(&self.board));
+            }
+            if !running {
+                topped_out = true;
+                break;
+            }
+        }
+
+        GameResult {
+            lines: self.lines,
+            pieces: self.pieces,
+            score: self.score,
+            max_height,
+            topped_out,
+        }
+    }
+}
+
+fn stack_height(board: &[Vec<u8>]) -> u32 {
+    column_heights(board).into_iter().max().unwrap_or(0) as u32
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::evaluator::LinearEvaluator;
+
+    #[test]
+    fn test_limits() {
+        let evaluator = LinearEvaluator::classic(crate::BEST_WEIGHTS);
+
+        for simulation in [Simulation::Frames, Simulation::Placements].iter() {
+            let limits = GameLimits {
+                pieces: 40,
+                ..GameLimits::default()
+            };
+            let result = Game::with_seed(2).simulate(&evaluator, *simulation, &limits);
+            assert_eq!(result.pieces, 40);
+            assert_eq!(result.score, result.pieces + 10 * result.lines);
+            assert!(!result.topped_out);
+            assert!(result.max_height > 0);
+
+            let limits = GameLimits {
+                lines: 2,
+                ..GameLimits::default()
+            };
+            let result = Game::with_seed(2).simulate(&evaluator, *simulation, &limits);
+            assert!(result.lines >= 2 && result.lines < 6);
+        }
+    }
+
+    #[test]
+    fn test_suite() {
+        let stats = SuiteStats::new(&[4, 1, 10, 5]);
+        assert_eq!(stats.mean, 5.0);
+        assert_eq!(stats.median, 4.5);
+        assert_eq!(stats.std_dev, 10.5f64.sqrt());
+        assert_eq!(SuiteStats::new(&[7, 1, 2]).median, 2.0);
+
+        // the same seeds within a generation, new ones in the next
+        assert_eq!(suite_seeds(9, 3, 4), suite_seeds(9, 3, 4));
+        assert!(!suite_seeds(9, 4, 4)
+            .iter()
+            .any(|seed| suite_seeds(9, 3, 4).contains(seed)));
+    }
+
+    #[test]
+    fn test_topped_out() {
+        // the blocks reach almost to the top, so the first pieces end the game
+        let mut game = Game::with_seed(2);
+        for row in 3..HEIGHT as usize {
+            game.board[row] = vec![1; WIDTH as usize];
+        }
+
+        let evaluator = LinearEvaluator::classic(crate::BEST_WEIGHTS);
+        let result = game.simulate(&evaluator, Simulation::Placements, &GameLimits::default());
+        assert!(result.topped_out);
+        assert!(result.max_height > HEIGHT as u32 - 3);
+    }
+}
@@ -0,0 +1,272 @@
+use crate::tetris::*;
+use std::fmt::Display;
+use std::fs;
+use std::str::FromStr;
+
+// The first line of every snapshot, so the format can be changed later on without
+// misreading older files.
+const SNAPSHOT_HEADER: &str = "tetris-snapshot 1";
+
+// The randomizer is restored by drawing its pieces again, so a snapshot can't ask for more
+// draws than this. The bot would play for months to place this many pieces.
+const MAX_DRAWS: u64 = 100_000_000;
+
+// Saving and restoring the full state of a game as plain text. Every field is written on
+// its own "name values..." line, followed by the board with one digit per block:
+//
+// tetris-snapshot 1
+// score 12
+// pieces 11
+// lines 0
+// gravity_counter 3
+// randomizer 7 11
+// current 1 0 3 4
+// next 2
+// target 3 1 16 4
+// board
+// 0000000000
+// ...
+//
+// The target line is only written while the bot has a placement for the current piece, and
+// snapshots written before the lines were counted have no lines line. Every other line is
+// required.
+impl Game {
+    pub fn to_snapshot(&self) -> String {
+        let mut snapshot = String::new();
+
+        snapshot.push_str(&format!("{}\n", SNAPSHOT_HEADER));
+        snapshot.push_str(&format!("score {}\n", self.score));
+        snapshot.push_str(&format!("pieces {}\n", self.pieces));
+        snapshot.push_str(&format!("lines {}\n", self.lines));
+        snapshot.push_str(&format!("gravity_counter {}\n", self.gravity_counter));
+        snapshot.push_str(&format!(
+            "randomizer {} {}\n",
+            self.randomizer.seed, self.randomizer.draws
+        ));
+        snapshot.push_str(&format!("current {}\n", piece_to_string(&self.curr_piece)));
+        snapshot.push_str(&format!("next {}\n", self.next_piece_kind));
+        if let Some(plan) = &self.plan {
+            snapshot.push_str(&format!("target {}\n", piece_to_string(&plan.target)));
+        }
+
+        snapshot.push_str("board\n");
+        for row in self.board.iter() {
+            let line: String = row.iter().map(|block| block.to_string()).collect();
+            snapshot.push_str(&line);
+            snapshot.push('\n');
+        }
+
+        snapshot
+    }
+
+    pub fn from_snapshot(snapshot: &str) -> Result<Game, String> {
+        let mut lines = snapshot.lines();
+
+        if lines.next() != Some(SNAPSHOT_HEADER) {
+            return Err("not a snapshot".to_string());
+        }
+
+        let mut game = Game::new();
+        let mut score = None;
+        let mut pieces = None;
+        let mut gravity_counter = None;
+        let mut randomizer = None;
+        let mut current = None;
+        let mut next = None;
+        let mut target = None;
+
+        for line in &mut lines {
+            if line == "board" {
+                break;
+            }
+
+            let values: Vec<&str> = line.split_whitespace().collect();
+            match values.split_first() {
+                Some((&"score", [value])) => score = Some(parse_value(line, value)?),
+                Some((&"pieces", [value])) => pieces = Some(parse_value(line, value)?),
+                Some((&"lines", [value])) => game.lines = parse_value(line, value)?,
+                Some((&"gravity_counter", [value])) => {
+                    gravity_counter = Some(parse_value(line, value)?)
+                }
+                Some((&"randomizer", [seed, draws])) => {
+                    randomizer = Some((parse_value(line, seed)?, parse_value(line, draws)?))
+                }
+                Some((&"current", values)) => current = Some(piece_from_values(line, values)?),
+                Some((&"next", [value])) => next = Some(parse_kind(line, value)?),
+                Some((&"target", values)) => target = Some(piece_from_values(line, values)?),
+                _ => return Err(format!("invalid line '{}'", line)),
+            }
+        }
+
+        game.score = score.ok_or("the score is missing")?;
+        game.pieces = pieces.ok_or("the pieces are missing")?;
+        game.gravity_counter = gravity_counter.ok_or("the gravity_counter is missing")?;
+        let (seed, draws) = randomizer.ok_or("the randomizer is missing")?;
+        // every placed piece drew the next one
+        if draws > game.pieces as u64 || draws > MAX_DRAWS {
+            return Err(format!(
+                "the randomizer can't have drawn {} pieces in {} placed ones",
+                draws, game.pieces
+            ));
+        }
+        game.randomizer = Randomizer::restore(seed, draws);
+        game.curr_piece = current.ok_or("the current piece is missing")?;
+        game.next_piece_kind = next.ok_or("the next piece is missing")?;
+        game.plan = target.map(|target| BotPlan::new(target, game.curr_piece));
+        // the path of the bot is planned again from the current piece
+        if let Some(plan) = &mut game.plan {
+            plan.expected = game.curr_piece;
+        }
+
+        for row in 0..HEIGHT as usize {
+            let line = lines.next().ok_or("the board is incomplete")?;
+            if line.len() != WIDTH as usize {
+                return Err(format!("invalid board row '{}'", line));
+            }
+
+            for (col, block) in line.chars().enumerate() {
+                game.board[row][col] = block
+                    .to_digit(10)
+                    .filter(|block| *block <= 7)
+                    .ok_or(format!("invalid board row '{}'", line))?
+                    as u8;
+            }
+        }
+
+        // the pieces are on the board, on empty cells
+        let target = game.plan.as_ref().map(|plan| plan.target);
+        for piece in std::iter::once(game.curr_piece).chain(target) {
+            if !game.does_piece_fit(piece.kind, piece.rotation, piece.x, piece.y) {
+                return Err(format!(
+                    "the piece '{}' doesn't fit the board",
+                    piece_to_string(&piece)
+                ));
+            }
+        }
+
+        Ok(game)
+    }
+
+    pub fn save_snapshot(&self, path: &str) -> Result<(), String> {
+        fs::write(path, self.to_snapshot()).map_err(|e| e.to_string())
+    }
+
+    pub fn load_snapshot(path: &str) -> Result<Game, String> {
+        let snapshot = fs::read_to_string(path).map_err(|e| e.to_string())?;
+        Game::from_snapshot(&snapshot)
+    }
+}
+
+fn piece_to_string(piece: &Piece) -> String {
+    format!("{} {} {} {}", piece.kind, piece.rotation, piece.x, piece.y)
+}
+
+// A piece with its 4x4 box at least partly on the board, whether it fits is checked with
+// the board
+fn piece_from_values(line: &str, values: &[&str]) -> Result<Piece, String> {
+    let (kind, rotation, x, y) = match values {
+        [kind, rotation, x, y] => (
+            parse_kind(line, kind)?,
+            parse_value::<u8>(line, rotation)?,
+            parse_value::<i8>(line, x)?,
+            parse_value::<i8>(line, y)?,
+        ),
+        _ => return Err(format!("invalid line '{}': a piece needs 4 values", line)),
+    };
+
+    if rotation >= 4 || x <= -4 || x >= HEIGHT as i8 || y <= -4 || y >= WIDTH as i8 {
+        return Err(format!(
+            "invalid line '{}': the piece is off the board",
+            line
+        ));
+    }
+
+    Ok(Piece {
+        kind,
+        rotation,
+        x,
+        y,
+    })
+}
+
+fn parse_kind(line: &str, value: &str) -> Result<usize, String> {
+    let kind = parse_value(line, value)?;
+    if kind >= 7 {
+        return Err(format!("invalid line '{}': unknown piece {}", line, kind));
+    }
+    Ok(kind)
+}
+
+fn parse_value<T>(line: &str, value: &str) -> Result<T, String>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    value
+        .parse::<T>()
+        .map_err(|e| format!("invalid line '{}': {}", line, e))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::evaluator::LinearEvaluator;
+
+    #[test]
+    fn test_snapshot_round_trip() {
+        let mut game = Game::with_seed(5);
+        for _ in 0..2000 {
+            game.update();
+            let action = game.bot(&LinearEvaluator::classic(crate::BEST_WEIGHTS));
+            game.apply_action(action);
+        }
+
+        let restored = Game::from_snapshot(&game.to_snapshot()).unwrap();
+        assert_eq!(restored.to_snapshot(), game.to_snapshot());
+
+        // the restored game should continue exactly like the original one
+        let mut restored = restored;
+        for _ in 0..500 {
+            game.update();
+            restored.update();
+            let action = game.bot(&LinearEvaluator::classic(crate::BEST_WEIGHTS));
+            game.apply_action(action);
+            let action = restored.bot(&LinearEvaluator::classic(crate::BEST_WEIGHTS));
+            restored.apply_action(action);
+        }
+        assert_eq!(restored.board, game.board);
+        assert_eq!(restored.next_piece_kind, game.next_piece_kind);
+    }
+
+    #[test]
+    fn test_invalid_snapshot() {
+        assert!(Game::from_snapshot("").is_err());
+        assert!(Game::from_snapshot("tetris-snapshot 1\nscore x\n").is_err());
+        assert!(Game::from_snapshot("tetris-snapshot 1\nboard\n0000\n").is_err());
+
+        let snapshot = Game::with_seed(5).to_snapshot();
+        let with_line = |name: &str, line: &str| {
+            let lines: Vec<&str> = snapshot
+                .lines()
+                .map(|other| if other.starts_with(name) { line } else { other })
+                .filter(|line| !line.is_empty())
+                .collect();
+            Game::from_snapshot(&lines.join("\n"))
+        };
+        assert!(with_line("current", "current 2 1 5 3").is_ok());
+        assert!(with_line("current", "current 2 1 -2 5").is_err());
+        assert!(with_line("current", "current 1 0 18 0").is_err());
+        assert!(with_line("board", "target 6 1 17 0\nboard").is_ok());
+        assert!(with_line("board", "target 6 0 17 0\nboard").is_err());
+        assert!(with_line("current", "current 9 0 0 0").is_err());
+        assert!(with_line("current", "current 1 4 0 0").is_err());
+        assert!(with_line("current", "current 1 0 300 0").is_err());
+        assert!(with_line("current", "current 1 0 0 20").is_err());
+        assert!(with_line("next", "next 7").is_err());
+        assert!(with_line("score", "score -1").is_err());
+        assert!(with_line("pieces", "pieces 5000000000").is_err());
+        assert!(with_line("randomizer", "randomizer 5 18446744073709551615").is_err());
+        // a required line left out
+        assert!(with_line("randomizer", "").is_err());
+    }
+}
@@ -0,0 +1,242 @@
+use crate::tetris::MoveAction::*;
+use crate::tetris::*;
+use std::io::{self, Write};
+
+// letters of the piece kinds, used by the plain ASCII dump
+const PIECE_LETTERS: [char; 7] = ['S', 'T', 'Z', 'L', 'O', 'J', 'I'];
+
+impl Game {
+    // Plain ASCII picture of the board with the current piece on it, one line per row.
+    // Blocks are shown with the letter of their piece, empty blocks with a dot.
+    pub fn to_ascii(&self) -> String {
+        let board = self.board_with_current_piece();
+        let mut ascii = String::new();
+
+        for row in board.iter() {
+            ascii.push('|');
+            for block in row.iter() {
+                ascii.push(match block {
+                    0 => '.',
+                    _ => PIECE_LETTERS[*block as usize - 1],
+                });
+            }
+            ascii.push_str("|\n");
+        }
+        ascii.push('+');
+        ascii.push_str(&"-".repeat(WIDTH as usize));
+        ascii.push_str("+\n");
+
+        ascii
+    }
+
+    // copy of the board with the blocks of the current piece added, if it fits
+    pub fn board_with_current_piece(&self) -> Vec<Vec<u8>> {
+        let mut board = self.board.clone();
+
+        for (row, col) in Game::piece_blocks(&self.curr_piece).iter() {
+            if *row >= 0 && *row < HEIGHT as i8 && *col >= 0 && *col < WIDTH as i8 {
+                board[*row as usize][*col as usize] = self.curr_piece.kind as u8 + 1;
+            }
+        }
+
+        board
+    }
+}
+
+// Drawing the game into an ANSI terminal. Every frame is drawn over the previous one, so
+// the terminal doesn't scroll.
+pub struct TerminalRenderer {
+    // the first frame clears the screen
+    cleared: bool,
+}
+
+impl TerminalRenderer {
+    pub fn new() -> Self {
+        TerminalRenderer { cleared: false }
+    }
+
+    pub fn draw(&mut self, game: &Game) {
+        let mut frame = String::new();
+
+        if !self.cleared {
+            // clear the screen and hide the cursor
+            frame.push_str("\x1b[2J\x1b[?25l");
+            self.cleared = true;
+        }
+        // move the cursor to the top left corner
+        frame.push_str("\x1b[H");
+
+        let board = game.board_with_current_piece();
+        let preview = Game::piece_blocks(&Piece {
+            kind: game.next_piece_kind,
+            rotation: 0,
+            x: 0,
+            y: 0,
+        });
+
+        for (row, line) in board.iter().enumerate() {
+            frame.push_str("\x1b[0m|");
+            for block in line.iter() {
+                frame.push_str(&block_color(*block));
+                frame.push_str("  ");
+            }
+            frame.push_str("\x1b[0m|  ");
+
+            // the next piece and the score on the right side of the board
+            match row {
+                0 => frame.push_str("Next"),
+                1..=4 => {
+                    for col in 0..4 {
+                        if preview.contains(&(row as i8 - 1, col)) {
+                            frame.push_str(&block_color(game.next_piece_kind as u8 + 1));
+                        } else {
+                            frame.push_str("\x1b[0m");
+                        }
+                        frame.push_str("  ");
+                    }
+                    frame.push_str("\x1b[0m");
+                }
+                6 => frame.push_str(&format!("Score {}", game.score)),
+                7 => frame.push_str(&format!("Pieces {}", game.pieces)),
+                _ => {}
+            }
+            // clear the rest of the line
+            frame.push_str("\x1b[K\n");
+        }
+        frame.push('+');
+        frame.push_str(&"--".repeat(WIDTH as usize));
+        frame.push_str("+\x1b[K\n");
+
+        let mut stdout = io::stdout();
+        stdout.write_all(frame.as_bytes()).unwrap();
+        stdout.flush().unwrap();
+    }
+
+    // the board with the final score below it
+    pub fn draw_game_over(&mut self, game: &Game) {
+        self.draw(game);
+        print!(
+            "GAME OVER, score {}\x1b[K\nPress any key to play again, q to quit\x1b[K\n",
+            game.score
+        );
+        io::stdout().flush().unwrap();
+    }
+}
+
+impl Drop for TerminalRenderer {
+    // restore the cursor and the colors
+    fn drop(&mut self) {
+        print!("\x1b[0m\x1b[?25h");
+        io::stdout().flush().unwrap();
+    }
+}
+
+// Switching the terminal into raw mode while alive: key presses are available immediately,
+// they are not echoed, and reading stdin doesn't block.
+pub struct RawMode {
+    original: libc::termios,
+}
+
+impl RawMode {
+    pub fn enable() -> io::Result<Self> {
+        unsafe {
+            let mut original: libc::termios = std::mem::zeroed();
+            if libc::tcgetattr(libc::STDIN_FILENO, &mut original) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+
+            let mut raw = original;
+            raw.c_lflag &= !(libc::ICANON | libc::ECHO | libc::ISIG);
+            raw.c_cc[libc::VMIN] = 0;
+            raw.c_cc[libc::VTIME] = 0;
+            if libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &raw) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+
+            Ok(RawMode { original })
+        }
+    }
+
+    // Reading the keys pressed since the last call. Unknown keys are returned as NONE.
+    pub fn read_actions(&self) -> Vec<MoveAction> {
+        let mut buffer = [0u8; 64];
+        let count = unsafe {
+            libc::read(
+                libc::STDIN_FILENO,
+                buffer.as_mut_ptr() as *mut libc::c_void,
+                buffer.len(),
+            )
+        };
+        if count <= 0 {
+            return Vec::new();
+        }
+
+        let keys = &buffer[..count as usize];
+        let mut actions = Vec::new();
+        let mut idx = 0;
+        while idx < keys.len() {
+            // arrow keys are sent as ESC [ A-D
+            if keys[idx] == 27 && keys.get(idx + 1) == Some(&b'[') && idx + 2 < keys.len() {
+                actions.push(match keys[idx + 2] {
+                    b'A' => ROTATE,
+                    b'B' => DOWN,
+                    b'C' => RIGHT,
+                    b'D' => LEFT,
+                    _ => NONE,
+                });
+                idx += 3;
+                continue;
+            }
+
+            actions.push(match keys[idx] {
+                b'a' => LEFT,
+                b'd' => RIGHT,
+                b's' => DOWN,
+                b'w' | b' ' => ROTATE,
+                // Esc, q and Ctrl-C
+                27 | b'q' | 3 => QUIT,
+                _ => NONE,
+            });
+            idx += 1;
+        }
+
+        actions
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        unsafe {
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &self.original);
+        }
+    }
+}
+
+// ANSI background color of a block, the same colors the SDL window uses
+fn block_color(block: u8) -> String {
+    if block == 0 {
+        return "\x1b[48;2;0;0;0m".to_string();
+    }
+    format!("\x1b[48;2;128;{};{}m", block * (255 / 7), block * (255 / 7))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_to_ascii() {
+        let mut game = Game::new();
+        game.board[HEIGHT as usize - 1] = vec![1, 2, 3, 4, 5, 6, 7, 0, 0, 0];
+
+        let ascii = game.to_ascii();
+        let lines: Vec<&str> = ascii.lines().collect();
+
+        assert_eq!(lines.len(), HEIGHT as usize + 1);
+        assert_eq!(lines[0], "|......S...|");
+        assert_eq!(lines[1], "|......SS..|");
+        assert_eq!(lines[2], "|.......S..|");
+        assert_eq!(lines[HEIGHT as usize - 1], "|STZLOJI...|");
+        assert_eq!(lines[HEIGHT as usize], "+----------+");
+    }
+}
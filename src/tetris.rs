@@ -1,5 +1,9 @@
+use crate::evaluator::Evaluator;
+use crate::placement::{moved_piece, Placement};
+use crate::search::{SearchConfig, SearchStats};
 use crate::tetris::MoveAction::{DOWN, LEFT, RIGHT, ROTATE};
-use core::cmp;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 use std::iter::Iterator;
 
 pub(crate) const SCREEN_WIDTH: u32 = 400;
@@ -7,6 +11,8 @@ pub(crate) const SCREEN_HEIGHT: u32 = 800;
 pub(crate) const RECT_DIM: u32 = 40;
 pub(crate) const WIDTH: u8 = (SCREEN_WIDTH / RECT_DIM) as u8;
 pub(crate) const HEIGHT: u8 = (SCREEN_HEIGHT / RECT_DIM) as u8;
+// the piece is forced down after this many frames
+pub(crate) const GRAVITY_FRAMES: usize = 20;
 
 #[warn(non_upper_case_globals)]
 pub static tetrominos: [[u8; 16]; 7] = [
@@ -19,7 +25,7 @@ pub static tetrominos: [[u8; 16]; 7] = [
     [0, 1, 0, 0, 0, 1, 0, 0, 0, 1, 0, 0, 0, 1, 0, 0], // I
 ];
 
-#[derive(PartialEq)]
+#[derive(PartialEq, Copy, Clone, Debug)]
 pub enum MoveAction {
     LEFT,
     RIGHT,
@@ -29,7 +35,7 @@ pub enum MoveAction {
     QUIT,
 }
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub struct Piece {
     pub kind: usize,
     pub rotation: u8,
@@ -37,16 +43,85 @@ pub struct Piece {
     pub y: i8,
 }
 
+// Deterministic source of the upcoming pieces. Games started with the same seed get the
+// same piece sequence, which is what makes replays possible.
+#[derive(Clone)]
+pub struct Randomizer {
+    pub seed: u64,
+    // number of pieces generated so far
+    pub draws: u64,
+    rng: StdRng,
+}
+
+impl Randomizer {
+    pub fn new(seed: u64) -> Self {
+        Randomizer {
+            seed,
+            draws: 0,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    // Recreating a randomizer which already generated @draws pieces
+    pub fn restore(seed: u64, draws: u64) -> Self {
+        let mut randomizer = Randomizer::new(seed);
+        for _ in 0..draws {
+            randomizer.next_kind();
+        }
+        randomizer
+    }
+
+    pub fn next_kind(&mut self) -> usize {
+        self.draws += 1;
+        self.rng.gen::<usize>() % 7
+    }
+}
+
+// The placement the bot steers the current piece to, with the moves left to get there
+#[derive(Clone, Debug, PartialEq)]
+pub struct BotPlan {
+    pub target: Piece,
+    // the moves in reverse order, the next one is the last
+    pub moves: Vec<MoveAction>,
+    // where the piece should be when the bot moves next
+    pub expected: Piece,
+}
+
+impl BotPlan {
+    pub fn new(target: Piece, expected: Piece) -> Self {
+        BotPlan {
+            target,
+            moves: Vec::new(),
+            expected,
+        }
+    }
+}
+
+#[derive(Clone)]
 pub struct Game {
     pub board: Vec<Vec<u8>>,
     pub score: u32,
     pub curr_piece: Piece,
     pub next_piece_kind: usize,
-    pub target_piece: Piece,
+    // where the bot is taking the current piece, None until the bot searched for it
+    pub plan: Option<BotPlan>,
+    pub randomizer: Randomizer,
+    // frames elapsed since the piece was last forced down
+    pub gravity_counter: usize,
+    // number of pieces locked into the board so far
+    pub pieces: u32,
+    // number of lines cleared so far
+    pub lines: u32,
+    // the work done by the bot searches of the game so far
+    pub bot_stats: SearchStats,
 }
 
 impl Game {
     pub fn new() -> Self {
+        Game::with_seed(0)
+    }
+
+    pub fn with_seed(seed: u64) -> Self {
         Game {
             board: vec![vec![0u8; 10]; 20],
             score: 0,
@@ -57,12 +132,66 @@ impl Game {
                 y: 5,
             },
             next_piece_kind: 0,
-            target_piece: Piece {
-                kind: 100,
-                rotation: 100,
-                x: 100,
-                y: 100,
-            },
+            plan: None,
+            randomizer: Randomizer::new(seed),
+            gravity_counter: 0,
+            pieces: 0,
+            lines: 0,
+            bot_stats: SearchStats::default(),
+        }
+    }
+
+    // Advancing the game by one frame. In every few frames the current piece is forced
+    // down, and if it can't move then it gets locked. Returns false if the game is over.
+    pub fn update(&mut self) -> bool {
+        self.gravity_counter += 1;
+
+        if self.gravity_counter > GRAVITY_FRAMES {
+            self.gravity_counter = 0;
+
+            if !self.move_piece_down() {
+                return self.lock_piece();
+            }
+        }
+
+        true
+    }
+
+    // Adding the current piece to the board and spawning the next one. Returns false if the
+    // next piece does not fit, which means the game is over.
+    pub fn lock_piece(&mut self) -> bool {
+        self.add_current_piece();
+        let kind = self.next_piece_kind;
+
+        if !self.does_piece_fit(kind, 0, 0, 5) {
+            return false;
+        }
+
+        self.score += 1;
+        self.pieces += 1;
+        self.find_and_remove_solved_lines();
+
+        self.curr_piece = Piece {
+            kind,
+            rotation: 0,
+            x: 0,
+            y: 5,
+        };
+        self.next_piece_kind = self.randomizer.next_kind();
+        self.plan = None;
+
+        true
+    }
+
+    // Executing the given player (or bot) action on the current piece. Returns whether the
+    // piece moved.
+    pub fn apply_action(&mut self, action: MoveAction) -> bool {
+        match action {
+            LEFT => self.move_piece_left(),
+            RIGHT => self.move_piece_right(),
+            DOWN => self.move_piece_down(),
+            ROTATE => self.rotate_piece(),
+            _ => false,
         }
     }
 
@@ -76,6 +205,23 @@ impl Game {
         }
     }
 
+    // The absolute (row, col) coordinates of the blocks of the given piece
+    pub fn piece_blocks(piece: &Piece) -> [(i8, i8); 4] {
+        let mut blocks = [(0i8, 0i8); 4];
+        let mut idx = 0;
+
+        for row in 0..4u8 {
+            for col in 0..4u8 {
+                if tetrominos[piece.kind][Game::rotate(row, col, piece.rotation)] != 0 {
+                    blocks[idx] = (piece.x + row as i8, piece.y + col as i8);
+                    idx += 1;
+                }
+            }
+        }
+
+        blocks
+    }
+
     pub fn does_piece_fit(&self, tetr_idx: usize, rotation: u8, x: i8, y: i8) -> bool {
         for tetr_x in 0..4u8 {
             for tetr_y in 0..4u8 {
@@ -155,6 +301,7 @@ impl Game {
 
         // updating the score
         self.score += found_lines * 10;
+        self.lines += found_lines;
     }
 
     // Updating the board by setting the blocks where the current piece is to @value
@@ -332,62 +479,6 @@ impl Game {
         overall_filledness_factor as u64
     }
 
-    fn fitness(&mut self, fitness_params: [u64; 6]) -> u64 {
-        let mut filled_lines = 0;
-
-        for row in 0..HEIGHT as usize {
-            let mut filled = true;
-            for col in 0..WIDTH as usize {
-                if self.board[row][col] == 0 {
-                    filled = false;
-                    break;
-                }
-            }
-            if filled {
-                filled_lines += 1;
-            }
-        }
-
-        filled_lines *= 10;
-        filled_lines = filled_lines * filled_lines;
-
-        let hole_fact = self.blocks_hole_factor();
-        let hole_fact = hole_fact * hole_fact;
-        let (bumpiness, max_height) = self.blocks_bumpiness();
-        let continuity = self.block_line_continuity();
-        let filledness = self.block_line_filledness();
-
-        let fitness = 1_000_000_000_000_000_000u64;
-        let fitness = cmp::max(
-            0,
-            fitness - (fitness_params[0] as f64 * 10f64 * hole_fact as f64) as u64,
-        );
-        let fitness = cmp::max(0, fitness - fitness_params[1] * 2500 * bumpiness);
-        let fitness = cmp::max(
-            0,
-            fitness - (fitness_params[2] as f64 * 20f64 * max_height as f64) as u64,
-        );
-        let fitness = cmp::max(
-            0,
-            fitness + (fitness_params[3] as f64 / 50f64 * continuity as f64) as u64,
-        );
-        let fitness = cmp::max(
-            0,
-            fitness + (fitness_params[4] as f64 * 50f64 * filledness as f64) as u64,
-        );
-        let fitness = cmp::max(
-            0,
-            fitness + (fitness_params[5] as f64 * 300f64 * filled_lines as f64) as u64,
-        );
-
-        // println!(
-        //     "hole_fact[{}], bumpiness[{}], continuity[{}], filled_lines[{}] => fintess[{}]",
-        //     hole_fact, bumpiness, continuity, filled_lines, fitness
-        // );
-
-        fitness
-    }
-
     // minor run time optimization
     // some pieces symmetric, therefore pointless to test some of its rotations
     // for example: cube (name: O) rotation is pointless
@@ -399,130 +490,85 @@ impl Game {
         }
     }
 
-    pub fn bot(&mut self, fitness_params: [u64; 6]) -> MoveAction {
-        let kind = self.curr_piece.kind;
-        let mut best_fitness = 0u64;
-        let mut best_piece = self.curr_piece;
-        let original_piece = self.curr_piece; // save the original values
+    pub fn bot<E: Evaluator>(&mut self, evaluator: &E) -> MoveAction {
+        self.bot_with_config(evaluator, &SearchConfig::default())
+    }
 
-        if self.target_piece.kind == 100 {
-            for rotation in 0..Game::max_rotation(kind) {
-                for col in 0..WIDTH as i8 + 5 {
-                    let col = col - 2;
-                    let mut curr_piece_x = self.curr_piece.x;
+    pub fn bot_with_config<E: Evaluator>(
+        &mut self,
+        evaluator: &E,
+        config: &SearchConfig,
+    ) -> MoveAction {
+        let mut plan = match self.plan.take() {
+            Some(plan) => plan,
+            None => BotPlan::new(self.search_target(evaluator, config), self.curr_piece),
+        };
+
+        // The piece is not where the plan expected it: a move failed, or gravity took it
+        // somewhere else. The rest of the path is planned again from where the piece is.
+        let diverged = plan.expected != self.curr_piece;
+        if diverged {
+            self.bot_stats.replans += 1;
+        }
+        let at_target = Placement::new(self.curr_piece) == Placement::new(plan.target)
+            && !self.move_piece_fits(DOWN);
+
+        if diverged || (plan.moves.is_empty() && !at_target) {
+            plan.moves = match self.plan_path(&Placement::new(plan.target)) {
+                Some(path) => path,
+                // the target can't be reached anymore, searching again from here
+                None => {
+                    plan.target = self.search_target(evaluator, config);
+                    self.plan_path(&Placement::new(plan.target))
+                        .unwrap_or_default()
+                }
+            };
+            plan.moves.reverse();
+        }
 
-                    // if the piece cannot even placed then continue with next iteration
-                    if !self.does_piece_fit(kind, rotation, curr_piece_x, col) {
-                        continue;
-                    }
+        // at the target the piece is pushed down until it locks
+        let action = plan.moves.pop().unwrap_or(DOWN);
 
-                    // pushing down the piece until it would stuck into its final place
-                    while self.does_piece_fit(kind, rotation, curr_piece_x + 1, col) {
-                        curr_piece_x += 1;
-                    }
+        // where the piece will be in the next frame, gravity included
+        plan.expected = self.curr_piece;
+        if self.move_piece_fits(action) {
+            plan.expected = moved_piece(&plan.expected, action);
+        }
+        let falling = Piece {
+            x: plan.expected.x + 1,
+            ..plan.expected
+        };
+        if self.gravity_counter + 1 > GRAVITY_FRAMES
+            && self.does_piece_fit(falling.kind, falling.rotation, falling.x, falling.y)
+        {
+            plan.expected = falling;
+        }
 
-                    // evaluate the resulting game board goodness
-
-                    // Step 1: adding the piece to the board
-                    self.curr_piece = Piece {
-                        kind,
-                        rotation,
-                        x: curr_piece_x,
-                        y: col,
-                    };
-                    self.add_current_piece();
-
-                    let kind_2 = self.next_piece_kind;
-                    self.curr_piece = Piece {
-                        kind: self.next_piece_kind,
-                        rotation: 0,
-                        x: 0,
-                        y: 5,
-                    };
-                    let original_piece_2 = self.curr_piece;
-
-                    for rotation_2 in 0..Game::max_rotation(kind_2) {
-                        for col_2 in 0..WIDTH as i8 + 5 {
-                            let col_2 = col_2 - 2;
-                            let mut curr_piece_x_2 = self.curr_piece.x;
-
-                            // if the piece cannot even placed then continue with next iteration
-                            if !self.does_piece_fit(kind_2, rotation_2, curr_piece_x_2, col_2) {
-                                continue;
-                            }
-
-                            // pushing down the piece until it would stuck into its final place
-                            while self.does_piece_fit(kind_2, rotation_2, curr_piece_x_2 + 1, col_2) {
-                                curr_piece_x_2 += 1;
-                            }
-
-                            // evaluate the resulting game board goodness
-
-                            // Step 1: adding the piece to the board
-                            self.curr_piece = Piece {
-                                kind: kind_2,
-                                rotation: rotation_2,
-                                x: curr_piece_x_2,
-                                y: col_2,
-                            };
-                            self.add_current_piece();
-
-                            // Step 2: calculate the fitness
-                            let fitness = self.fitness(fitness_params);
-
-                            // Step 3: remove the piece and restore position
-                            self.remove_current_piece();
-                            self.curr_piece = original_piece_2;
-
-                            // check whether this move is better then the current best
-                            if fitness > best_fitness {
-                                best_piece = Piece {
-                                    kind,
-                                    rotation,
-                                    x: curr_piece_x,
-                                    y: col,
-                                };
-                                best_fitness = fitness;
-                            }
-                        }
-                    }
+        self.plan = Some(plan);
+        action
+    }
 
-                    // adding back the outer loops piece to be able to remove it
-                    self.curr_piece = Piece {
-                        kind,
-                        rotation,
-                        x: curr_piece_x,
-                        y: col,
-                    };
-
-                    // Step 3: remove the piece and restore position
-                    self.remove_current_piece();
-                    self.curr_piece = original_piece;
-                }
-            }
+    // Locking the current piece at once where the bot would take it, without playing the
+    // frames in between, and spawning the next one. Returns false if the game is over.
+    pub fn bot_place_piece<E: Evaluator>(&mut self, evaluator: &E, config: &SearchConfig) -> bool {
+        self.curr_piece = self.search_target(evaluator, config);
+        self.gravity_counter = 0;
 
-            self.target_piece = best_piece;
-        }
-        // decide the next move based on the best_piece coordinate and rotation
-        // on default just push it down
-        let mut bot_action = DOWN;
+        self.lock_piece()
+    }
 
-        if original_piece.rotation != self.target_piece.rotation {
-            bot_action = ROTATE;
-        }
-        // if the @y coordinates are different then
-        else if original_piece.y != self.target_piece.y {
-            // move RIGHT if the current piece is left to the best move
-            if original_piece.y < self.target_piece.y {
-                bot_action = RIGHT;
-            }
-            // else the current piece is on the right to the best move so move LEFT
-            else {
-                bot_action = LEFT;
-            }
-        }
+    // the best placement of the current piece, the current position if it can't be placed
+    fn search_target<E: Evaluator>(&mut self, evaluator: &E, config: &SearchConfig) -> Piece {
+        let queue = [self.curr_piece.kind, self.next_piece_kind];
+        let (best, stats) = self.search_placement(evaluator, &queue, config);
+        self.bot_stats.add(&stats);
 
-        bot_action
+        best.unwrap_or(self.curr_piece)
+    }
+
+    fn move_piece_fits(&self, action: MoveAction) -> bool {
+        let moved = moved_piece(&self.curr_piece, action);
+        self.does_piece_fit(moved.kind, moved.rotation, moved.x, moved.y)
     }
 }
 
@@ -594,4 +640,48 @@ mod test {
         game.remove_current_piece();
         assert_eq!(game.board[0][6], 0);
     }
+
+    #[test]
+    fn test_bot_replans() {
+        let evaluator = crate::evaluator::LinearEvaluator::classic(crate::BEST_WEIGHTS);
+        let mut game = Game::with_seed(4);
+        let action = game.bot(&evaluator);
+        game.apply_action(action);
+        let target = game.plan.as_ref().unwrap().target;
+        assert_eq!(game.bot_stats.replans, 0);
+
+        // pushing the piece away from the plan, the bot notices and still locks the piece
+        // at the target
+        game.curr_piece.y += if target.y > game.curr_piece.y { -1 } else { 1 };
+        let mut expected = game.clone();
+        expected.curr_piece = target;
+        expected.add_current_piece();
+        while game.pieces == 0 {
+            game.update();
+            let action = game.bot(&evaluator);
+            game.apply_action(action);
+        }
+        assert_eq!(game.bot_stats.replans, 1);
+        assert_eq!(game.board, expected.board);
+    }
+
+    #[test]
+    fn test_bot_place_piece() {
+        let evaluator = crate::evaluator::LinearEvaluator::classic(crate::BEST_WEIGHTS);
+        let mut frames = Game::with_seed(3);
+        let mut placements = Game::with_seed(3);
+
+        // locking the pieces at once ends in the same game as playing every frame
+        while frames.pieces < 30 {
+            frames.update();
+            let action = frames.bot(&evaluator);
+            frames.apply_action(action);
+        }
+        for _ in 0..30 {
+            assert!(placements.bot_place_piece(&evaluator, &SearchConfig::default()));
+        }
+        assert_eq!(placements.pieces, 30);
+        assert_eq!(placements.board, frames.board);
+        assert_eq!(placements.curr_piece.kind, frames.curr_piece.kind);
+    }
 }
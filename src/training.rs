@@ -0,0 +1,763 @@
+use crate::evaluator::*;
+use crate::genetic::*;
+use crate::simulation::*;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::fmt::Display;
+use std::fs;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+// The first line of every checkpoint, like the header of the snapshots
+const CHECKPOINT_HEADER: &str = "tetris-checkpoint 1";
+
+// set by Ctrl-C while the training runs
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+// An individual of the genetic training: the weights, and how well they played
+#[derive(Debug, PartialEq, Clone)]
+pub struct DNA {
+    pub params: Vec<u64>,
+    pub score: u64,
+    pub stats: SuiteStats,
+}
+
+impl DNA {
+    pub fn new<R: Rng>(rng: &mut R, max_val: u64, param_count: usize) -> Self {
+        DNA {
+            params: (0..param_count)
+                .map(|_| rng.gen::<u64>() % max_val)
+                .collect(),
+            score: 0,
+            stats: SuiteStats::default(),
+        }
+    }
+
+    fn to_line(&self, name: &str) -> String {
+        let params: Vec<String> = self.params.iter().map(u64::to_string).collect();
+        format!(
+            "{} {} {} {} {} {}\n",
+            name,
+            self.score,
+            self.stats.mean,
+            self.stats.median,
+            self.stats.std_dev,
+            params.join(" ")
+        )
+    }
+
+    fn from_values(line: &str, values: &[&str]) -> Result<DNA, String> {
+        if values.len() < 4 {
+            return Err(format!("invalid line '{}': missing values", line));
+        }
+
+        Ok(DNA {
+            params: values[4..]
+                .iter()
+                .map(|value| parse_number(line, value))
+                .collect::<Result<Vec<u64>, String>>()?,
+            score: parse_number(line, values[0])?,
+            stats: SuiteStats {
+                mean: parse_number(line, values[1])?,
+                median: parse_number(line, values[2])?,
+                std_dev: parse_number(line, values[3])?,
+            },
+        })
+    }
+}
+
+// Settings of the genetic training of the evaluator weights, read from a small TOML file
+// with one "name = value" line per setting, comments start with a #. Settings which are left
+// out keep their default:
+//
+// # the genetic algorithm
+// population_size = 100
+// parents_ratio = 2
+// mutation_probability = 20
+// selection = "truncation"
+// tournament_size = 3
+// crossover = "uniform"
+// blend_alpha = 0.5
+// mutation = "scaled"
+// mutation_sigma = 0.1
+// max_param_value = 1000000
+// target_score = 1000000
+// max_generation = 1000
+// checkpoint_interval = 1
+// # the games every individual plays
+// seed = 0
+// games = 5
+// simulation = "placements"
+// max_pieces = 5000
+// max_lines = 0
+// objective = "score"
+// features = ["hole_factor", "bumpiness", "max_height"]
+// # where the results are written
+// output_dir = "training"
+#[derive(Clone, Debug, PartialEq)]
+pub struct TrainingConfig {
+    pub population_size: usize,
+    // the best 1 / @parents_ratio of the population are the parents of the next generation
+    pub parents_ratio: usize,
+    // one in this many children gets a mutation
+    pub mutation_probability: usize,
+    // the genetic operators, see genetic.rs; @tournament_size is used by the tournament
+    // selection, @blend_alpha by the blend crossover and @mutation_sigma by the gaussian
+    // mutation
+    pub selection: SelectionMethod,
+    pub tournament_size: usize,
+    pub crossover: CrossoverMethod,
+    pub blend_alpha: f64,
+    pub mutation: MutationMethod,
+    pub mutation_sigma: f64,
+    // the weights are between 0 and this
+    pub max_param_value: u64,
+    // the training stops when an individual reaches this score, or after @max_generation
+    pub target_score: u64,
+    pub max_generation: u64,
+    // a checkpoint is written after every this many generations
+    pub checkpoint_interval: u64,
+    // the seed of every random number of the training, and of the first games (see
+    // suite_seeds)
+    pub seed: u64,
+    pub games: usize,
+    pub simulation: Simulation,
+    pub limits: GameLimits,
+    pub objective: Objective,
+    pub features: Vec<Feature>,
+    // the copy of the config and the results of the generations are written here
+    pub output_dir: String,
+}
+
+impl Default for TrainingConfig {
+    fn default() -> Self {
+        TrainingConfig {
+            population_size: 100,
+            parents_ratio: 2,
+            mutation_probability: 20,
+            selection: SelectionMethod::Truncation,
+            tournament_size: 3,
+            crossover: CrossoverMethod::Uniform,
+            blend_alpha: 0.5,
+            mutation: MutationMethod::Scaled,
+            mutation_sigma: 0.1,
+            max_param_value: 1_000_000,
+            target_score: 1_000_000,
+            max_generation: 1000,
+            checkpoint_interval: 1,
+            seed: 0,
+            games: 5,
+            simulation: Simulation::Placements,
+            limits: GameLimits {
+                pieces: 5000,
+                lines: 0,
+                time_ms: 0,
+            },
+            objective: Objective::Score,
+            features: CLASSIC_FEATURES.to_vec(),
+            output_dir: "training".to_string(),
+        }
+    }
+}
+
+impl TrainingConfig {
+    pub fn parents_size(&self) -> usize {
+        self.population_size / self.parents_ratio
+    }
+
+    pub fn load(path: &str) -> Result<TrainingConfig, String> {
+        let text = fs::read_to_string(path).map_err(|e| e.to_string())?;
+        TrainingConfig::from_toml(&text)
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        fs::write(path, self.to_toml()).map_err(|e| e.to_string())
+    }
+
+    pub fn from_toml(text: &str) -> Result<TrainingConfig, String> {
+        let mut config = TrainingConfig::default();
+
+        for line in text.lines().map(strip_comment).map(str::trim) {
+            if line.is_empty() {
+                continue;
+            }
+
+            let (name, value) = line
+                .split_once('=')
+                .ok_or(format!("invalid line '{}'", line))?;
+            let value = value.trim();
+
+            match name.trim() {
+                "population_size" => config.population_size = parse_number(line, value)?,
+                "parents_ratio" => config.parents_ratio = parse_number(line, value)?,
+                "mutation_probability" => config.mutation_probability = parse_number(line, value)?,
+                "selection" => {
+                    config.selection = SelectionMethod::from_name(parse_string(line, value)?)?
+                }
+                "tournament_size" => config.tournament_size = parse_number(line, value)?,
+                "crossover" => {
+                    config.crossover = CrossoverMethod::from_name(parse_string(line, value)?)?
+                }
+                "blend_alpha" => config.blend_alpha = parse_number(line, value)?,
+                "mutation" => {
+                    config.mutation = MutationMethod::from_name(parse_string(line, value)?)?
+                }
+                "mutation_sigma" => config.mutation_sigma = parse_number(line, value)?,
+                "max_param_value" => config.max_param_value = parse_number(line, value)?,
+                "target_score" => config.target_score = parse_number(line, value)?,
+                "max_generation" => config.max_generation = parse_number(line, value)?,
+                "checkpoint_interval" => config.checkpoint_interval = parse_number(line, value)?,
+                "seed" => config.seed = parse_number(line, value)?,
+                "games" => config.games = parse_number(line, value)?,
+                "simulation" => {
+                    config.simulation = Simulation::from_name(parse_string(line, value)?)?
+                }
+                "max_pieces" => config.limits.pieces = parse_number(line, value)?,
+                "max_lines" => config.limits.lines = parse_number(line, value)?,
+                "max_time_ms" => config.limits.time_ms = parse_number(line, value)?,
+                "objective" => config.objective = Objective::from_name(parse_string(line, value)?)?,
+                "features" => {
+                    config.features = parse_strings(line, value)?
+                        .into_iter()
+                        .map(Feature::from_name)
+                        .collect::<Result<Vec<Feature>, String>>()?
+                }
+                "output_dir" => config.output_dir = parse_string(line, value)?.to_string(),
+                name => return Err(format!("unknown setting '{}'", name)),
+            }
+        }
+
+        config.check()?;
+        Ok(config)
+    }
+
+    // the genetic operators chosen by the settings
+    pub fn operators(&self) -> GeneticOperators {
+        GeneticOperators {
+            selection: match self.selection {
+                SelectionMethod::Truncation => Box::new(Truncation),
+                SelectionMethod::Tournament => Box::new(Tournament {
+                    size: self.tournament_size,
+                }),
+                SelectionMethod::Roulette => Box::new(Roulette),
+                SelectionMethod::Rank => Box::new(Rank),
+            },
+            crossover: match self.crossover {
+                CrossoverMethod::Uniform => Box::new(UniformCrossover),
+                CrossoverMethod::OnePoint => Box::new(OnePointCrossover),
+                CrossoverMethod::Blend => Box::new(BlendCrossover {
+                    alpha: self.blend_alpha,
+                }),
+            },
+            mutation: match self.mutation {
+                MutationMethod::Gaussian => Box::new(GaussianMutation {
+                    sigma: self.mutation_sigma,
+                }),
+                MutationMethod::Scaled => Box::new(ScaledMutation),
+                MutationMethod::Reset => Box::new(ResetMutation),
+            },
+        }
+    }
+
+    // the settings the genetic algorithm can't work with
+    pub fn check(&self) -> Result<(), String> {
+        if self.parents_ratio == 0 || self.parents_size() < 2 {
+            return Err("the population needs at least two parents".to_string());
+        }
+        if self.mutation_probability == 0
+            || self.max_param_value == 0
+            || self.checkpoint_interval == 0
+            || self.games == 0
+        {
+            return Err(
+                "mutation_probability, max_param_value, checkpoint_interval and games can't be 0"
+                    .to_string(),
+            );
+        }
+        if self.tournament_size == 0 {
+            return Err("the tournament needs at least one individual".to_string());
+        }
+        // NaN fails both comparisons
+        let valid_alpha = self.blend_alpha >= 0.0;
+        let valid_sigma = self.mutation_sigma > 0.0;
+        if !valid_alpha || !valid_sigma {
+            return Err(
+                "blend_alpha can't be negative and mutation_sigma must be positive".to_string(),
+            );
+        }
+        // the results of the training depend only on the seed, not on the speed of the
+        // machine
+        if self.limits.time_ms > 0 {
+            return Err("the games of the training can't have a time limit".to_string());
+        }
+        if self.features.is_empty() {
+            return Err("no features to train".to_string());
+        }
+        Ok(())
+    }
+
+    // The settings a resumed training must have in common with the one it continues: all
+    // of them except when to stop, how often to write a checkpoint and where
+    pub fn resume_settings(&self) -> Vec<String> {
+        const FREE: [&str; 4] = [
+            "target_score",
+            "max_generation",
+            "checkpoint_interval",
+            "output_dir",
+        ];
+        self.to_toml()
+            .lines()
+            .filter(|line| !FREE.contains(&line.split(" = ").next().unwrap_or("")))
+            .map(str::to_string)
+            .collect()
+    }
+
+    // every setting, also the default ones, so the file alone repeats the training
+    pub fn to_toml(&self) -> String {
+        let features: Vec<String> = self
+            .features
+            .iter()
+            .map(|feature| format!("\"{}\"", feature.name()))
+            .collect();
+
+        format!(
+            "population_size = {}\n\
+             parents_ratio = {}\n\
+             mutation_probability = {}\n\
+             selection = \"{}\"\n\
+             tournament_size = {}\n\
+             crossover = \"{}\"\n\
+             blend_alpha = {}\n\
+             mutation = \"{}\"\n\
+             mutation_sigma = {}\n\
+             max_param_value = {}\n\
+             target_score = {}\n\
+             max_generation = {}\n\
+             checkpoint_interval = {}\n\
+             seed = {}\n\
+             games = {}\n\
+             simulation = \"{}\"\n\
+             max_pieces = {}\n\
+             max_lines = {}\n\
+             objective = \"{}\"\n\
+             features = [{}]\n\
+             output_dir = \"{}\"\n",
+            self.population_size,
+            self.parents_ratio,
+            self.mutation_probability,
+            self.selection.name(),
+            self.tournament_size,
+            self.crossover.name(),
+            self.blend_alpha,
+            self.mutation.name(),
+            self.mutation_sigma,
+            self.max_param_value,
+            self.target_score,
+            self.max_generation,
+            self.checkpoint_interval,
+            self.seed,
+            self.games,
+            self.simulation.name(),
+            self.limits.pieces,
+            self.limits.lines,
+            self.objective.name(),
+            features.join(", "),
+            self.output_dir
+        )
+    }
+}
+
+// The state of the training after a generation, enough to continue it exactly where it
+// stopped. The random numbers of the training come from generators seeded with @rng_seed
+// (the seed of the config) and the generation, see generation_rng, so the seed is the whole
+// state of the random numbers. The setting lines are the settings of the config the
+// training has to be continued with, see resume_settings:
+//
+// tetris-checkpoint 1
+// generation 12
+// rng_seed 5208361
+// setting population_size = 100
+// ...
+// best 1510 1510.4 1498 33.1 519244 220500 228269 724455 719307 226769
+// dna 0 0 0 0 519244 220500 228269 724455 719307 226769
+// ...
+//
+// The values of a DNA are its score, the mean, median and standard deviation of its games,
+// then its weights. The dna lines are the population of the next generation.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Checkpoint {
+    pub generation: u64,
+    pub rng_seed: u64,
+    pub settings: Vec<String>,
+    pub best: DNA,
+    pub population: Vec<DNA>,
+}
+
+impl Checkpoint {
+    pub fn to_text(&self) -> String {
+        let mut text = format!(
+            "{}\ngeneration {}\nrng_seed {}\n",
+            CHECKPOINT_HEADER, self.generation, self.rng_seed
+        );
+        for setting in self.settings.iter() {
+            text.push_str(&format!("setting {}\n", setting));
+        }
+        text.push_str(&self.best.to_line("best"));
+        for dna in self.population.iter() {
+            text.push_str(&dna.to_line("dna"));
+        }
+
+        text
+    }
+
+    pub fn from_text(text: &str) -> Result<Checkpoint, String> {
+        let mut lines = text.lines();
+        if lines.next() != Some(CHECKPOINT_HEADER) {
+            return Err("not a checkpoint".to_string());
+        }
+
+        let mut generation = None;
+        let mut rng_seed = None;
+        let mut settings = Vec::new();
+        let mut best = None;
+        let mut population = Vec::new();
+
+        for line in lines {
+            let values: Vec<&str> = line.split_whitespace().collect();
+            match values.split_first() {
+                Some((&"generation", [value])) => generation = Some(parse_number(line, value)?),
+                Some((&"rng_seed", [value])) => rng_seed = Some(parse_number(line, value)?),
+                // the values of a setting can have spaces
+                Some((&"setting", [_, ..])) => {
+                    settings.push(line.trim()["setting".len()..].trim().to_string())
+                }
+                Some((&"best", values)) => best = Some(DNA::from_values(line, values)?),
+                Some((&"dna", values)) => population.push(DNA::from_values(line, values)?),
+                _ => return Err(format!("invalid line '{}'", line)),
+            }
+        }
+
+        Ok(Checkpoint {
+            generation: generation.ok_or("the generation is missing")?,
+            rng_seed: rng_seed.ok_or("the rng_seed is missing")?,
+            settings,
+            best: best.ok_or("the best DNA is missing")?,
+            population,
+        })
+    }
+
+    pub fn load(path: &str) -> Result<Checkpoint, String> {
+        let text = fs::read_to_string(path).map_err(|e| e.to_string())?;
+        Checkpoint::from_text(&text)
+    }
+
+    // whether the training of the config can continue from the checkpoint
+    pub fn check(&self, config: &TrainingConfig) -> Result<(), String> {
+        if self.population.len() != config.population_size {
+            return Err(format!(
+                "the checkpoint has {} individuals, the config {}",
+                self.population.len(),
+                config.population_size
+            ));
+        }
+        let params = std::iter::once(&self.best).chain(self.population.iter());
+        if params
+            .map(|dna| dna.params.len())
+            .any(|len| len != config.features.len())
+        {
+            return Err(format!(
+                "the weights of the checkpoint don't match the {} features of the config",
+                config.features.len()
+            ));
+        }
+        if self.rng_seed != config.seed {
+            return Err(format!(
+                "the checkpoint was made with the seed {}, the config has {}",
+                self.rng_seed, config.seed
+            ));
+        }
+        let settings = config.resume_settings();
+        for (saved, setting) in self.settings.iter().zip(settings.iter()) {
+            if saved != setting {
+                return Err(format!(
+                    "the checkpoint was made with '{}', the config has '{}'",
+                    saved, setting
+                ));
+            }
+        }
+        if self.settings.len() != settings.len() {
+            return Err("the settings of the checkpoint don't match the config".to_string());
+        }
+        Ok(())
+    }
+
+    // Written next to the old checkpoint first, then moved over it, so an interrupted save
+    // doesn't destroy the old checkpoint
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let new_path = format!("{}.new", path);
+        fs::write(&new_path, self.to_text()).map_err(|e| e.to_string())?;
+        fs::rename(&new_path, path).map_err(|e| e.to_string())
+    }
+}
+
+// The random numbers of the training come from separate generators: one for the
+// @generation-th generation, and one for every individual of the generation, the one
+// making the @idx-th individual of the next generation. Every generator has its own well
+// mixed seed, so they don't depend on the order they are used in, nor on the seeds of the
+// games, which follow each other from the same seed.
+pub fn generation_rng(rng_seed: u64, generation: u64) -> StdRng {
+    StdRng::seed_from_u64(mix(mix(rng_seed) ^ generation))
+}
+
+pub fn individual_rng(rng_seed: u64, generation: u64, idx: usize) -> StdRng {
+    StdRng::seed_from_u64(mix(mix(mix(rng_seed) ^ generation) ^ idx as u64))
+}
+
+// the splitmix64 finalizer, like the keys of the transposition table
+fn mix(value: u64) -> u64 {
+    let mut z = value.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+extern "C" fn on_interrupt(_signal: libc::c_int) {
+    INTERRUPTED.store(true, Ordering::SeqCst);
+}
+
+// From now on Ctrl-C doesn't kill the program, it only sets the flag of is_interrupted, so
+// the training can write a checkpoint before it stops
+pub fn catch_interrupt() {
+    unsafe {
+        libc::signal(
+            libc::SIGINT,
+            on_interrupt as *const () as libc::sighandler_t,
+        );
+    }
+}
+
+pub fn is_interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+fn parse_number<T>(line: &str, value: &str) -> Result<T, String>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    value
+        .parse::<T>()
+        .map_err(|e| format!("invalid line '{}': {}", line, e))
+}
+
+// the line without its comment, which starts with a # outside of the strings
+fn strip_comment(line: &str) -> &str {
+    let mut in_string = false;
+    for (idx, c) in line.char_indices() {
+        match c {
+            '"' => in_string = !in_string,
+            '#' if !in_string => return &line[..idx],
+            _ => (),
+        }
+    }
+    line
+}
+
+// a string in double quotes
+fn parse_string<'a>(line: &str, value: &'a str) -> Result<&'a str, String> {
+    value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+        .ok_or(format!("invalid line '{}': expected a string", line))
+}
+
+// an array of strings, like ["a", "b"]
+fn parse_strings<'a>(line: &str, value: &'a str) -> Result<Vec<&'a str>, String> {
+    value
+        .strip_prefix('[')
+        .and_then(|value| value.strip_suffix(']'))
+        .ok_or(format!("invalid line '{}': expected an array", line))?
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(|item| parse_string(line, item))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_config_round_trip() {
+        let config = TrainingConfig::from_toml(
+            "# a short training\n\
+             population_size = 20 # a small population\n\
+             games = 3\n\
+             simulation = \"frames\"\n\
+             max_lines = 50\n\
+             objective = \"lines\"\n\
+             selection = \"tournament\"\n\
+             crossover = \"blend\"\n\
+             blend_alpha = 0.25\n\
+             features = [\"holes\", \"landing_height\",]\n\
+             output_dir = \"runs/#2\" # a # in a string\n",
+        )
+        .unwrap();
+
+        assert_eq!(config.population_size, 20);
+        assert_eq!(config.parents_size(), 10);
+        assert_eq!(config.games, 3);
+        assert_eq!(config.simulation, Simulation::Frames);
+        assert_eq!(config.limits.lines, 50);
+        assert_eq!(config.limits.pieces, 5000);
+        assert_eq!(config.objective, Objective::Lines);
+        assert_eq!(config.selection, SelectionMethod::Tournament);
+        assert_eq!(config.crossover, CrossoverMethod::Blend);
+        assert_eq!(config.blend_alpha, 0.25);
+        assert_eq!(config.mutation, MutationMethod::Scaled);
+        assert_eq!(
+            config.features,
+            vec![Feature::Holes, Feature::LandingHeight]
+        );
+        assert_eq!(config.output_dir, "runs/#2");
+
+        assert_eq!(TrainingConfig::from_toml(&config.to_toml()), Ok(config));
+        assert_eq!(
+            TrainingConfig::from_toml(&TrainingConfig::default().to_toml()),
+            Ok(TrainingConfig::default())
+        );
+    }
+
+    #[test]
+    fn test_checkpoint_round_trip() {
+        let mut rng = generation_rng(7, 3);
+        let mut best = DNA::new(&mut rng, 1000, 6);
+        best.score = 1510;
+        best.stats = SuiteStats::new(&[1498, 1490, 1542]);
+        let mut config = TrainingConfig {
+            population_size: 4,
+            seed: 7,
+            features: vec![Feature::Holes; 6],
+            ..TrainingConfig::default()
+        };
+        let checkpoint = Checkpoint {
+            generation: 3,
+            rng_seed: 7,
+            settings: config.resume_settings(),
+            best,
+            population: (0..4).map(|_| DNA::new(&mut rng, 1000, 6)).collect(),
+        };
+
+        assert_eq!(
+            Checkpoint::from_text(&checkpoint.to_text()),
+            Ok(checkpoint.clone())
+        );
+        assert!(Checkpoint::from_text("tetris-checkpoint 1\ngeneration 3\n").is_err());
+        assert!(Checkpoint::from_text("tetris-checkpoint 1\nbest 1 2\n").is_err());
+
+        // only a config with the same settings continues it, it can stop at another time
+        config.max_generation = 5;
+        config.output_dir = "elsewhere".to_string();
+        assert_eq!(checkpoint.check(&config), Ok(()));
+        let changes: [fn(&mut TrainingConfig); 7] = [
+            |config| config.population_size = 6,
+            |config| config.features.truncate(5),
+            |config| config.seed = 8,
+            |config| config.games = 3,
+            |config| config.max_param_value = 1000,
+            |config| config.selection = SelectionMethod::Rank,
+            |config| config.mutation_sigma = 0.2,
+        ];
+        for change in changes.iter() {
+            let mut changed = config.clone();
+            change(&mut changed);
+            assert!(checkpoint.check(&changed).is_err());
+        }
+
+        // the same generation gets the same random numbers
+        assert_eq!(
+            generation_rng(7, 3).gen::<u64>(),
+            generation_rng(7, 3).gen::<u64>()
+        );
+    }
+
+    #[test]
+    fn test_random_streams() {
+        let first = |mut rng: StdRng| rng.gen::<u64>();
+
+        // the same seed, generation and individual always get the same numbers
+        assert_eq!(
+            first(individual_rng(7, 3, 5)),
+            first(individual_rng(7, 3, 5))
+        );
+
+        // and different ones get different numbers
+        let streams = [
+            first(generation_rng(7, 3)),
+            first(generation_rng(8, 3)),
+            first(generation_rng(7, 4)),
+            first(individual_rng(7, 3, 0)),
+            first(individual_rng(7, 3, 1)),
+            first(individual_rng(7, 4, 1)),
+            first(StdRng::seed_from_u64(7)),
+        ];
+        for (idx, value) in streams.iter().enumerate() {
+            assert!(!streams[idx + 1..].contains(value));
+        }
+    }
+
+    #[test]
+    fn test_reproducible_training() {
+        // two small trainings from the same seed end the same way
+        let run = |name: &str| {
+            let output_dir = std::env::temp_dir().join(format!(
+                "tetris-training-{}-{}",
+                name,
+                std::process::id()
+            ));
+            let config = TrainingConfig {
+                population_size: 4,
+                max_generation: 2,
+                seed: 3,
+                games: 2,
+                limits: GameLimits {
+                    pieces: 10,
+                    ..GameLimits::default()
+                },
+                output_dir: output_dir.to_string_lossy().to_string(),
+                ..TrainingConfig::default()
+            };
+            let features = config.features.clone();
+            crate::train(&config, false, |weights| {
+                let weights: Vec<f64> = weights.iter().map(|weight| *weight as f64).collect();
+                LinearEvaluator::new(&features, &weights)
+            })
+            .unwrap();
+
+            let checkpoint = Checkpoint::load(&format!("{}/checkpoint.txt", config.output_dir));
+            let results = fs::read_to_string(format!("{}/results.txt", config.output_dir));
+            fs::remove_dir_all(&output_dir).unwrap();
+            (checkpoint.unwrap(), results.unwrap())
+        };
+
+        let (checkpoint, results) = run("first");
+        assert_eq!(checkpoint.generation, 2);
+        assert_eq!(results.lines().count(), 2);
+        assert_eq!(run("second"), (checkpoint, results));
+    }
+
+    #[test]
+    fn test_invalid_config() {
+        assert!(TrainingConfig::from_toml("games 3").is_err());
+        assert!(TrainingConfig::from_toml("games = three").is_err());
+        assert!(TrainingConfig::from_toml("speed = 3").is_err());
+        assert!(TrainingConfig::from_toml("objective = lines").is_err());
+        assert!(TrainingConfig::from_toml("features = [\"nothing\"]").is_err());
+        assert!(TrainingConfig::from_toml("features = []").is_err());
+        assert!(TrainingConfig::from_toml("population_size = 3").is_err());
+        assert!(TrainingConfig::from_toml("selection = \"best\"").is_err());
+        assert!(TrainingConfig::from_toml("mutation_sigma = 0").is_err());
+        assert!(TrainingConfig::from_toml("max_time_ms = 100").is_err());
+    }
+}
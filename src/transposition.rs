@@ -0,0 +1,174 @@
+use crate::tetris::*;
+use std::sync::Mutex;
+
+// number of separately locked parts of a shared table
+const SHARDS: usize = 64;
+
+// Zobrist hashing of the positions of the search: every filled cell of the board and every
+// position of a piece has its own pseudo random key, and the hash of a position is the xor
+// of its keys. Adding the blocks of a piece to a board changes its hash with a few xors.
+
+// the @idx-th key, from the splitmix64 generator
+fn key(idx: u64) -> u64 {
+    let mut z = idx.wrapping_add(1).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+fn cell_key(row: i8, col: i8) -> u64 {
+    key(row as u64 * WIDTH as u64 + col as u64)
+}
+
+// the key of the piece position comes after the keys of the cells
+fn piece_key(piece: &Piece) -> u64 {
+    let cells = HEIGHT as u64 * WIDTH as u64;
+    let state = ((piece.kind as u64 * 4 + piece.rotation as u64) * 64 + (piece.x as u64 & 63)) * 64
+        + (piece.y as u64 & 63);
+
+    key(cells + state)
+}
+
+// hash of the filled cells of the board
+pub fn board_hash(board: &[Vec<u8>]) -> u64 {
+    let mut hash = 0;
+
+    for (row, line) in board.iter().enumerate() {
+        for (col, block) in line.iter().enumerate() {
+            if *block != 0 {
+                hash ^= cell_key(row as i8, col as i8);
+            }
+        }
+    }
+
+    hash
+}
+
+// hash of the board after the blocks of the piece are added to it
+pub fn with_piece_blocks(hash: u64, piece: &Piece) -> u64 {
+    Game::piece_blocks(piece)
+        .iter()
+        .fold(hash, |hash, (row, col)| hash ^ cell_key(*row, *col))
+}
+
+// hash of the board with the piece placed on it, where the piece position matters too
+pub fn placement_hash(hash: u64, piece: &Piece) -> u64 {
+    with_piece_blocks(hash, piece) ^ piece_key(piece)
+}
+
+// A fixed size cache of evaluations, indexed by the hash of the position. A new entry
+// replaces the older one at the same index.
+pub struct TranspositionTable {
+    entries: Vec<Option<(u64, f64)>>,
+}
+
+impl TranspositionTable {
+    pub fn new(size: usize) -> Self {
+        TranspositionTable {
+            entries: vec![None; size],
+        }
+    }
+
+    pub fn get(&self, hash: u64) -> Option<f64> {
+        match self.entries[hash as usize % self.entries.len()] {
+            Some((entry_hash, value)) if entry_hash == hash => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn insert(&mut self, hash: u64, value: f64) {
+        let idx = hash as usize % self.entries.len();
+        self.entries[idx] = Some((hash, value));
+    }
+}
+
+// A transposition table shared by the threads of a search. The table is split into shards
+// with their own locks, chosen by the high bits of the hash, so the threads rarely wait for
+// each other.
+pub struct SharedTable {
+    shards: Vec<Mutex<TranspositionTable>>,
+}
+
+impl SharedTable {
+    pub fn new(size: usize) -> Self {
+        let shard_size = size.div_ceil(SHARDS).max(1);
+        SharedTable {
+            shards: (0..SHARDS)
+                .map(|_| Mutex::new(TranspositionTable::new(shard_size)))
+                .collect(),
+        }
+    }
+
+    pub fn get(&self, hash: u64) -> Option<f64> {
+        self.shard(hash).lock().unwrap().get(hash)
+    }
+
+    pub fn insert(&self, hash: u64, value: f64) {
+        self.shard(hash).lock().unwrap().insert(hash, value);
+    }
+
+    fn shard(&self, hash: u64) -> &Mutex<TranspositionTable> {
+        &self.shards[(hash >> 48) as usize % SHARDS]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_hash_transpositions() {
+        let first = Piece {
+            kind: 4,
+            rotation: 0,
+            x: 17,
+            y: 0,
+        };
+        let second = Piece {
+            kind: 6,
+            rotation: 1,
+            x: 17,
+            y: 4,
+        };
+
+        // the same board reached in a different order has the same hash
+        let mut game = Game::new();
+        let empty = board_hash(&game.board);
+        let one_way = with_piece_blocks(with_piece_blocks(empty, &first), &second);
+        let other_way = with_piece_blocks(with_piece_blocks(empty, &second), &first);
+        assert_eq!(one_way, other_way);
+
+        game.curr_piece = first;
+        game.add_current_piece();
+        game.curr_piece = second;
+        game.add_current_piece();
+        assert_eq!(board_hash(&game.board), one_way);
+
+        // the piece position is part of the placement hash
+        assert_ne!(
+            placement_hash(empty, &first),
+            with_piece_blocks(empty, &first)
+        );
+    }
+
+    #[test]
+    fn test_table() {
+        let mut table = TranspositionTable::new(8);
+        table.insert(3, 1.5);
+        assert_eq!(table.get(3), Some(1.5));
+        assert_eq!(table.get(11), None);
+
+        // the same index, the newer entry wins
+        table.insert(11, 2.5);
+        assert_eq!(table.get(11), Some(2.5));
+        assert_eq!(table.get(3), None);
+
+        // the shards keep the entries of their hashes apart
+        let shared = SharedTable::new(SHARDS);
+        shared.insert(3, 1.5);
+        shared.insert(3 | 1 << 48, 2.5);
+        assert_eq!(shared.get(3), Some(1.5));
+        assert_eq!(shared.get(3 | 1 << 48), Some(2.5));
+        assert_eq!(shared.get(11), None);
+    }
+}